@@ -1,5 +1,5 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::{
         sse::{Event, KeepAlive},
@@ -9,38 +9,62 @@ use axum::{
     Router,
 };
 use pubky::{PublicKey, Pubky};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::convert::Infallible;
 use std::sync::Arc;
 use tokio::sync::broadcast;
-use tokio_stream::wrappers::BroadcastStream;
-use tokio_stream::StreamExt;
+use tokio_stream::wrappers::ReceiverStream;
 use tower_http::cors::CorsLayer;
 use tracing::{error, info};
 
 use crate::config::Config;
-use crate::db::{self, Db};
-use crate::pixel::PICO8_PALETTE;
+use crate::db;
+use crate::events::EventBus;
+use crate::metrics::{Metrics, SubscriberGuard};
+use crate::store::CanvasStore;
 use crate::watcher::SseEvent;
 
 #[derive(Clone)]
 pub struct AppState {
-    pub db: Db,
+    pub db: Arc<dyn CanvasStore>,
     pub pubky: Arc<Pubky>,
     pub config: Config,
-    pub sse_tx: broadcast::Sender<SseEvent>,
+    pub events: Arc<dyn EventBus>,
+    pub metrics: Arc<Metrics>,
 }
 
 pub fn router(state: AppState) -> Router {
+    // Gate expensive write routes behind the `ingest` capability scope. Read
+    // routes (canvas, pixel, SSE, feeds) stay public.
+    let auth_config = state.config.auth.clone().map(Arc::new);
+    let ingest_guard = axum::middleware::from_fn_with_state(
+        crate::auth::AuthContext::new(auth_config, crate::config::Scope::Ingest),
+        crate::auth::authorize,
+    );
+
     Router::new()
         .route("/api/canvas", get(get_canvas))
         .route("/api/canvas/pixel/{x}/{y}", get(get_pixel))
         .route("/api/canvas/meta", get(get_meta))
         .route("/api/canvas/palette", get(get_palette))
+        .route("/api/canvas.png", get(get_canvas_png))
+        .route("/api/canvas/packed", get(get_canvas_packed))
+        .route("/api/canvas/region", get(get_region))
+        .route("/api/canvas/region/packed", get(get_region_packed))
+        .route("/api/canvas/tile/{size}/{tx}/{ty}", get(get_tile_packed))
+        .route("/api/canvas/at", get(get_canvas_at))
         .route("/api/events", get(sse_events))
-        .route("/api/ingest/{public_key}", put(ingest_user))
+        .route("/api/stream", get(get_stream))
+        .route("/api/feed.rss", get(get_feed_rss))
+        .route("/api/feed.atom", get(get_feed_atom))
+        .route(
+            "/api/ingest/{public_key}",
+            put(ingest_user).route_layer(ingest_guard),
+        )
         .route("/api/user/{public_key}/credits", get(get_credits))
         .route("/api/user/{public_key}/profile", get(get_profile))
+        .route("/metrics", get(get_metrics))
         .layer(CorsLayer::permissive())
         .with_state(state)
 }
@@ -52,34 +76,32 @@ struct CanvasResponse {
 }
 
 async fn get_canvas(State(state): State<AppState>) -> Result<Json<CanvasResponse>, StatusCode> {
-    let db = state.db.clone();
-    let result = tokio::task::spawn_blocking(move || -> anyhow::Result<CanvasResponse> {
-        let size = db::get_canvas_size(&db)?;
-        let pixels = db::get_canvas_state(&db)?;
-        Ok(CanvasResponse { size, pixels })
-    })
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    .map_err(|e| {
-        error!("get_canvas error: {e:?}");
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    let size = state.db.canvas_size().await.map_err(internal("get_canvas"))?;
+    let pixels = state
+        .db
+        .canvas_state()
+        .await
+        .map_err(internal("get_canvas"))?;
+    Ok(Json(CanvasResponse { size, pixels }))
+}
 
-    Ok(Json(result))
+/// Map a store error to a 500 while logging it under `ctx`.
+fn internal(ctx: &'static str) -> impl Fn(anyhow::Error) -> StatusCode {
+    move |e| {
+        error!("{ctx} error: {e:?}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
 }
 
 async fn get_pixel(
     State(state): State<AppState>,
     Path((x, y)): Path<(u32, u32)>,
 ) -> Result<Json<db::PixelInfo>, StatusCode> {
-    let db = state.db.clone();
-    let result = tokio::task::spawn_blocking(move || db::get_pixel_info(&db, x, y))
+    let result = state
+        .db
+        .pixel_info(x, y)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .map_err(|e| {
-            error!("get_pixel error: {e:?}");
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .map_err(internal("get_pixel"))?;
 
     match result {
         Some(info) => Ok(Json(info)),
@@ -98,51 +120,571 @@ struct MetaResponse {
 }
 
 async fn get_meta(State(state): State<AppState>) -> Result<Json<MetaResponse>, StatusCode> {
-    let db = state.db.clone();
-    let config = state.config.clone();
-    let result = tokio::task::spawn_blocking(move || -> anyhow::Result<MetaResponse> {
-        let size = db::get_canvas_size(&db)?;
-        let (filled, overwritten) = db::get_fill_stats(&db)?;
-        Ok(MetaResponse {
-            size,
-            total_pixels: size * size,
-            filled,
-            overwritten,
-            max_credits: config.canvas.max_credits,
-            credit_regen_seconds: config.canvas.credit_regen_seconds,
-        })
-    })
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    .map_err(|e| {
-        error!("get_meta error: {e:?}");
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    let size = state.db.canvas_size().await.map_err(internal("get_meta"))?;
+    let (filled, overwritten) = state.db.fill_stats().await.map_err(internal("get_meta"))?;
+    Ok(Json(MetaResponse {
+        size,
+        total_pixels: size * size,
+        filled,
+        overwritten,
+        max_credits: state.config.canvas.max_credits,
+        credit_regen_seconds: state.config.canvas.credit_regen_seconds,
+    }))
+}
+
+async fn get_palette(State(state): State<AppState>) -> Result<Json<Vec<String>>, StatusCode> {
+    let palette = state.config.canvas.palette().map_err(internal("get_palette"))?;
+    Ok(Json(palette.colors().to_vec()))
+}
+
+#[derive(Deserialize)]
+struct AtQuery {
+    timestamp: i64,
+}
 
-    Ok(Json(result))
+/// Reconstruct the canvas as it looked at a past `timestamp` (unix microseconds)
+/// for r/place-style timelapse playback.
+async fn get_canvas_at(
+    State(state): State<AppState>,
+    Query(query): Query<AtQuery>,
+) -> Result<Json<CanvasResponse>, StatusCode> {
+    let size = state.db.canvas_size().await.map_err(internal("get_canvas_at"))?;
+    let pixels = state
+        .db
+        .reconstruct_at(query.timestamp)
+        .await
+        .map_err(internal("get_canvas_at"))?;
+    Ok(Json(CanvasResponse { size, pixels }))
+}
+
+/// Packed-snapshot binary: an 8-byte little-endian header (`width`, `height`)
+/// followed by the packed color buffer (two pixels per byte up to a 16-color
+/// palette, one byte per pixel beyond that — see `db::pack_pixel_states`).
+async fn get_canvas_packed(
+    State(state): State<AppState>,
+) -> Result<([(axum::http::HeaderName, &'static str); 1], Vec<u8>), StatusCode> {
+    let (width, height, packed) = state
+        .db
+        .canvas_packed()
+        .await
+        .map_err(internal("get_canvas_packed"))?;
+
+    Ok(packed_response(width, height, packed))
+}
+
+/// Inclusive viewport rectangle for the region queries.
+#[derive(Deserialize)]
+struct RegionQuery {
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+}
+
+/// Validate that `(x0, y0)`–`(x1, y1)` is non-inverted and inside the board.
+async fn resolve_region(
+    state: &AppState,
+    q: &RegionQuery,
+    ctx: &'static str,
+) -> Result<(), StatusCode> {
+    if q.x0 > q.x1 || q.y0 > q.y1 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let (width, height) = state.db.canvas_dims().await.map_err(internal(ctx))?;
+    if q.x1 >= width || q.y1 >= height {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    Ok(())
+}
+
+/// Fetch just the filled cells inside a viewport rectangle, so a panning client
+/// doesn't transfer the whole board.
+async fn get_region(
+    State(state): State<AppState>,
+    Query(query): Query<RegionQuery>,
+) -> Result<Json<CanvasResponse>, StatusCode> {
+    resolve_region(&state, &query, "get_region").await?;
+    let pixels = state
+        .db
+        .region(query.x0, query.y0, query.x1, query.y1)
+        .await
+        .map_err(internal("get_region"))?;
+    let size = query.x1 - query.x0 + 1;
+    Ok(Json(CanvasResponse { size, pixels }))
 }
 
-async fn get_palette() -> Json<Vec<&'static str>> {
-    Json(PICO8_PALETTE.to_vec())
+/// Packed viewport tile: an 8-byte little-endian header (`width`, `height`)
+/// followed by the tile-local packed buffer (see `get_canvas_packed`).
+async fn get_region_packed(
+    State(state): State<AppState>,
+    Query(query): Query<RegionQuery>,
+) -> Result<([(axum::http::HeaderName, &'static str); 1], Vec<u8>), StatusCode> {
+    resolve_region(&state, &query, "get_region_packed").await?;
+    let (width, height, packed) = state
+        .db
+        .region_packed(query.x0, query.y0, query.x1, query.y1)
+        .await
+        .map_err(internal("get_region_packed"))?;
+    Ok(packed_response(width, height, packed))
+}
+
+/// Fixed-size tile by grid coordinate, clipped to the canvas edge. Frontends
+/// request and cache whole tiles (e.g. 64×64) as they pan.
+async fn get_tile_packed(
+    State(state): State<AppState>,
+    Path((size, tx, ty)): Path<(u32, u32, u32)>,
+) -> Result<([(axum::http::HeaderName, &'static str); 1], Vec<u8>), StatusCode> {
+    if size == 0 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let (width, height) = state.db.canvas_dims().await.map_err(internal("get_tile_packed"))?;
+    let (x0, y0, mut x1, mut y1) = db::tile_bounds(size, tx, ty);
+    if x0 >= width || y0 >= height {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    // Clip the trailing tile to each board edge.
+    x1 = x1.min(width - 1);
+    y1 = y1.min(height - 1);
+    let (width, height, packed) = state
+        .db
+        .region_packed(x0, y0, x1, y1)
+        .await
+        .map_err(internal("get_tile_packed"))?;
+    Ok(packed_response(width, height, packed))
+}
+
+/// Frame a packed buffer behind the shared 8-byte dimension header.
+fn packed_response(
+    width: u32,
+    height: u32,
+    packed: Vec<u8>,
+) -> ([(axum::http::HeaderName, &'static str); 1], Vec<u8>) {
+    let mut body = Vec::with_capacity(8 + packed.len());
+    body.extend_from_slice(&width.to_le_bytes());
+    body.extend_from_slice(&height.to_le_bytes());
+    body.extend_from_slice(&packed);
+    (
+        [(axum::http::header::CONTENT_TYPE, "application/octet-stream")],
+        body,
+    )
+}
+
+#[derive(Deserialize)]
+struct PngQuery {
+    scale: Option<u32>,
+}
+
+async fn get_canvas_png(
+    State(state): State<AppState>,
+    Query(query): Query<PngQuery>,
+) -> Result<([(axum::http::HeaderName, &'static str); 1], Vec<u8>), StatusCode> {
+    let (width, height) = state
+        .db
+        .canvas_dims()
+        .await
+        .map_err(internal("get_canvas_png"))?;
+    let pixels = state
+        .db
+        .canvas_state()
+        .await
+        .map_err(internal("get_canvas_png"))?;
+
+    let palette = state.config.canvas.palette().map_err(internal("get_canvas_png"))?;
+    let scale = query.scale.unwrap_or(1).clamp(1, 64);
+    let png = crate::render::render_png(&pixels, &palette, width, height, scale)
+        .map_err(internal("get_canvas_png"))?;
+
+    Ok((
+        [(axum::http::header::CONTENT_TYPE, "image/png")],
+        png,
+    ))
+}
+
+/// Optional viewport filter for `/api/events`.
+///
+/// A client showing one zoomed-in tile can pass a bounding box (`x0,y0,x1,y1`,
+/// all inclusive) and an optional `users` CSV of public keys to receive only the
+/// `Pixel` events it actually renders. `Resize` events always pass through since
+/// they change which coordinates are valid.
+#[derive(Deserialize)]
+struct EventsQuery {
+    x0: Option<u32>,
+    y0: Option<u32>,
+    x1: Option<u32>,
+    y1: Option<u32>,
+    users: Option<String>,
+    /// Last changefeed sequence the client has already applied. When present the
+    /// stream opens with a catch-up of everything newer, so a reconnecting client
+    /// never has a gap between its snapshot and the live feed.
+    since: Option<i64>,
+}
+
+/// A validated server-side filter applied to the broadcast stream.
+struct EventFilter {
+    bbox: Option<(u32, u32, u32, u32)>,
+    users: Option<HashSet<String>>,
+}
+
+impl EventFilter {
+    fn allows(&self, event: &SseEvent) -> bool {
+        match event {
+            SseEvent::Resize { .. } => true,
+            SseEvent::Pixel { pixel: p, .. } => {
+                if let Some((x0, y0, x1, y1)) = self.bbox {
+                    if p.x < x0 || p.x > x1 || p.y < y0 || p.y > y1 {
+                        return false;
+                    }
+                }
+                if let Some(users) = &self.users {
+                    if !users.contains(&p.user_pk) {
+                        return false;
+                    }
+                }
+                true
+            }
+        }
+    }
 }
 
 async fn sse_events(
     State(state): State<AppState>,
-) -> Sse<impl tokio_stream::Stream<Item = Result<Event, Infallible>>> {
-    let rx = state.sse_tx.subscribe();
-    let stream = BroadcastStream::new(rx).filter_map(|msg| match msg {
-        Ok(event) => {
-            let data = serde_json::to_string(&event).unwrap_or_default();
-            let event_type = match &event {
-                SseEvent::Pixel(_) => "pixel",
-                SseEvent::Resize { .. } => "resize",
-            };
-            Some(Ok(Event::default().event(event_type).data(data)))
+    Query(query): Query<EventsQuery>,
+) -> Result<Sse<impl tokio_stream::Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    // Resolve the bounding box: all four corners must be present together, the
+    // rectangle must be non-inverted, and it must lie within the canvas.
+    let bbox = match (query.x0, query.y0, query.x1, query.y1) {
+        (None, None, None, None) => None,
+        (Some(x0), Some(y0), Some(x1), Some(y1)) => {
+            if x0 > x1 || y0 > y1 {
+                return Err(StatusCode::BAD_REQUEST);
+            }
+            let (width, height) = state.db.canvas_dims().await.map_err(internal("sse_events"))?;
+            if x1 >= width || y1 >= height {
+                return Err(StatusCode::BAD_REQUEST);
+            }
+            Some((x0, y0, x1, y1))
+        }
+        // A partial rectangle is ambiguous; reject it.
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    let users = query.users.map(|csv| {
+        csv.split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect::<HashSet<_>>()
+    });
+
+    let filter = EventFilter { bbox, users };
+
+    // Keep the subscriber gauge accurate for the lifetime of this stream.
+    let guard = SubscriberGuard::new(state.metrics.sse_subscribers.clone());
+
+    let mut broadcast_rx = state.events.subscribe();
+    let db = state.db.clone();
+    let since = query.since;
+
+    // Bridge the broadcast receiver into an mpsc the SSE body drains. Owning the
+    // receiver in a task lets us treat a `Lagged` overflow as a re-sync trigger —
+    // replaying the missed cells from the db — rather than a silent, unrecoverable
+    // gap, which is what dropping the stream (or ignoring the error) would be.
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<Event, Infallible>>(256);
+    tokio::spawn(async move {
+        // Hold the subscriber gauge for the lifetime of the task.
+        let _guard = guard;
+
+        // The client's position in the changefeed; a lag or reconnect resumes here.
+        let mut last_seq = since.unwrap_or(0);
+
+        // Snapshot-on-connect: flush everything newer than the client's cursor so
+        // it starts gap-free from wherever it left off.
+        if since.is_some() && resync(&db, &filter, last_seq, &tx, &mut last_seq).await.is_err() {
+            return;
+        }
+
+        loop {
+            match broadcast_rx.recv().await {
+                Ok(event) => {
+                    // Events buffered between `subscribe()` and the connect/Lagged
+                    // resync above were already replayed from the db; skip them
+                    // here so the client doesn't see them twice.
+                    if event.seq() <= last_seq {
+                        continue;
+                    }
+                    last_seq = event.seq();
+                    if filter.allows(&event) && tx.send(Ok(render_event(&event))).await.is_err() {
+                        break;
+                    }
+                }
+                // Slow subscriber: we dropped `n` messages. Re-sync from the db
+                // rather than leaving the client with a hole it can't detect.
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    if resync(&db, &filter, last_seq, &tx, &mut last_seq).await.is_err() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
         }
-        Err(_) => None,
     });
 
-    Sse::new(stream).keep_alive(KeepAlive::default())
+    let stream = ReceiverStream::new(rx);
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Render a live `SseEvent` as an SSE frame, tagging it with its changefeed
+/// sequence as the event id so a reconnecting client can resume from it.
+fn render_event(event: &SseEvent) -> Event {
+    let data = serde_json::to_string(event).unwrap_or_default();
+    let kind = match event {
+        SseEvent::Pixel { .. } => "pixel",
+        SseEvent::Resize { .. } => "resize",
+    };
+    Event::default().event(kind).id(event.seq().to_string()).data(data)
+}
+
+/// Replay every cell changed and every resize activated after `from` to a
+/// re-syncing client, advancing `cursor` to the new head. Used both on connect
+/// and on a `Lagged` overflow to give SSE consumers gap-free delivery under
+/// backpressure. Pixel and resize events are interleaved in `seq` order so a
+/// client applying them in sequence sees the same history a live subscriber
+/// would have.
+async fn resync(
+    db: &Arc<dyn CanvasStore>,
+    filter: &EventFilter,
+    from: i64,
+    tx: &tokio::sync::mpsc::Sender<Result<Event, Infallible>>,
+    cursor: &mut i64,
+) -> Result<(), ()> {
+    let (changes, head) = db.changes_since(from).await.map_err(|_| ())?;
+    let resizes = db.resizes_since(from).await.map_err(|_| ())?;
+
+    let mut events: Vec<SseEvent> = Vec::with_capacity(changes.len() + resizes.len());
+    events.extend(
+        changes
+            .into_iter()
+            .map(|(pixel, seq)| SseEvent::Pixel { pixel, seq }),
+    );
+    events.extend(resizes.into_iter().map(
+        |(old_width, old_height, new_width, new_height, seq)| SseEvent::Resize {
+            old_width,
+            old_height,
+            new_width,
+            new_height,
+            seq,
+        },
+    ));
+    events.sort_by_key(|event| event.seq());
+    // A replayed resize can sit past the pixel head, so count it too.
+    let replay_head = events.last().map_or(head, |event| event.seq().max(head));
+
+    for event in events {
+        if filter.allows(&event) {
+            tx.send(Ok(render_event(&event))).await.map_err(|_| ())?;
+        }
+    }
+    *cursor = (*cursor).max(replay_head);
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct StreamQuery {
+    since: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct StreamResponse {
+    /// The highest sequence reflected in `changes`; clients echo it back as `since`.
+    seq: i64,
+    /// Current canvas edge length — a growth here is the resize sentinel.
+    size: u32,
+    changes: Vec<db::PixelState>,
+}
+
+/// Incremental diff channel: a client passes its last-seen `since` sequence and
+/// receives only the cells changed after it, plus the new cursor. Omitting
+/// `since` (cold start) returns every cell — the same data as `/api/canvas`.
+async fn get_stream(
+    State(state): State<AppState>,
+    Query(query): Query<StreamQuery>,
+) -> Result<Json<StreamResponse>, StatusCode> {
+    let size = state.db.canvas_size().await.map_err(internal("get_stream"))?;
+    let (changes, seq) = state
+        .db
+        .changes_since(query.since.unwrap_or(0))
+        .await
+        .map_err(internal("get_stream"))?;
+    let changes = changes.into_iter().map(|(pixel, _)| pixel).collect();
+    Ok(Json(StreamResponse { seq, size, changes }))
+}
+
+async fn get_metrics(State(state): State<AppState>) -> Result<String, StatusCode> {
+    // Refresh canvas gauges from the store before encoding.
+    if let Ok((filled, overwritten)) = state.db.fill_stats().await {
+        state.metrics.pixels_filled.set(filled as i64);
+        state.metrics.pixels_overwritten.set(overwritten as i64);
+    }
+    state.metrics.encode().map_err(internal("get_metrics"))
+}
+
+#[derive(Deserialize)]
+struct FeedQuery {
+    limit: Option<u32>,
+}
+
+/// Default and hard-capped item counts for the activity feeds.
+const FEED_DEFAULT_LIMIT: u32 = 50;
+const FEED_MAX_LIMIT: u32 = 200;
+
+/// A single feed entry: a placement plus an optional resolved display name.
+struct FeedItem {
+    event: db::PixelEvent,
+    display_name: Option<String>,
+}
+
+/// Load the most recent placements and best-effort enrich each unique author
+/// with the display name from its `pubky.app/profile.json`.
+async fn load_feed_items(state: &AppState, limit: u32) -> Result<Vec<FeedItem>, StatusCode> {
+    let events = state
+        .db
+        .recent_events(limit)
+        .await
+        .map_err(internal("feed"))?;
+
+    let mut names: std::collections::HashMap<String, Option<String>> =
+        std::collections::HashMap::new();
+    for event in &events {
+        if !names.contains_key(&event.user_pk) {
+            let name = fetch_display_name(&state.pubky, &event.user_pk).await;
+            names.insert(event.user_pk.clone(), name);
+        }
+    }
+
+    Ok(events
+        .into_iter()
+        .map(|event| {
+            let display_name = names.get(&event.user_pk).cloned().flatten();
+            FeedItem {
+                event,
+                display_name,
+            }
+        })
+        .collect())
+}
+
+/// Best-effort fetch of a user's display name; never fails the feed.
+async fn fetch_display_name(pubky: &Pubky, public_key: &str) -> Option<String> {
+    let uri = format!("pubky://{public_key}/pub/pubky.app/profile.json");
+    let response = pubky.public_storage().get(&uri).await.ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+    let bytes = response.bytes().await.ok()?;
+    let profile: serde_json::Value = serde_json::from_slice(&bytes).ok()?;
+    profile
+        .get("name")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+}
+
+fn feed_author(item: &FeedItem) -> String {
+    match &item.display_name {
+        Some(name) => format!("{name} ({})", item.event.user_pk),
+        None => item.event.user_pk.clone(),
+    }
+}
+
+fn feed_limit(query: &FeedQuery) -> u32 {
+    query.limit.unwrap_or(FEED_DEFAULT_LIMIT).clamp(1, FEED_MAX_LIMIT)
+}
+
+/// Microsecond unix timestamp → `DateTime`, clamped to the epoch on overflow.
+fn micros_to_datetime(micros: i64) -> chrono::DateTime<chrono::Utc> {
+    chrono::DateTime::from_timestamp_micros(micros).unwrap_or_default()
+}
+
+async fn get_feed_rss(
+    State(state): State<AppState>,
+    Query(query): Query<FeedQuery>,
+) -> Result<([(axum::http::HeaderName, &'static str); 1], String), StatusCode> {
+    let items = load_feed_items(&state, feed_limit(&query)).await?;
+
+    let rss_items: Vec<rss::Item> = items
+        .iter()
+        .map(|item| {
+            let title = format!(
+                "Pixel ({}, {}) set to color {}",
+                item.event.x, item.event.y, item.event.color
+            );
+            rss::ItemBuilder::default()
+                .title(title)
+                .description(format!("Placed by {}", feed_author(item)))
+                .guid(
+                    rss::GuidBuilder::default()
+                        .value(item.event.id.clone())
+                        .permalink(false)
+                        .build(),
+                )
+                .pub_date(micros_to_datetime(item.event.placed_at).to_rfc2822())
+                .build()
+        })
+        .collect();
+
+    let channel = rss::ChannelBuilder::default()
+        .title("pubky-canva activity")
+        .link("/api/feed.rss")
+        .description("Recent pixel placements on the collaborative canvas")
+        .items(rss_items)
+        .build();
+
+    Ok((
+        [(axum::http::header::CONTENT_TYPE, "application/rss+xml")],
+        channel.to_string(),
+    ))
+}
+
+async fn get_feed_atom(
+    State(state): State<AppState>,
+    Query(query): Query<FeedQuery>,
+) -> Result<([(axum::http::HeaderName, &'static str); 1], String), StatusCode> {
+    let items = load_feed_items(&state, feed_limit(&query)).await?;
+
+    let updated = items
+        .first()
+        .map(|i| micros_to_datetime(i.event.placed_at))
+        .unwrap_or_default();
+
+    let entries: Vec<atom_syndication::Entry> = items
+        .iter()
+        .map(|item| {
+            let title = format!(
+                "Pixel ({}, {}) set to color {}",
+                item.event.x, item.event.y, item.event.color
+            );
+            atom_syndication::EntryBuilder::default()
+                .title(title)
+                .id(item.event.id.clone())
+                .updated(micros_to_datetime(item.event.placed_at))
+                .author(
+                    atom_syndication::PersonBuilder::default()
+                        .name(feed_author(item))
+                        .build(),
+                )
+                .build()
+        })
+        .collect();
+
+    let feed = atom_syndication::FeedBuilder::default()
+        .title("pubky-canva activity")
+        .id("/api/feed.atom")
+        .updated(updated)
+        .entries(entries)
+        .build();
+
+    Ok((
+        [(axum::http::header::CONTENT_TYPE, "application/atom+xml")],
+        feed.to_string(),
+    ))
 }
 
 async fn ingest_user(
@@ -150,34 +692,31 @@ async fn ingest_user(
     Path(public_key): Path<String>,
 ) -> Result<StatusCode, (StatusCode, String)> {
     // Check if user already exists
-    {
-        let db = state.db.clone();
-        let pk = public_key.clone();
-        let exists = tokio::task::spawn_blocking(move || db::user_exists(&db, &pk))
-            .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-        if exists {
-            return Ok(StatusCode::OK);
-        }
+    let exists = state
+        .db
+        .user_exists(&public_key)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if exists {
+        return Ok(StatusCode::OK);
     }
 
     // Resolve homeserver via Pkarr/DHT
-    let user_pk: PublicKey = public_key
-        .parse()
-        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid public key: {e}")))?;
+    let user_pk: PublicKey = public_key.parse().map_err(|e| {
+        state.metrics.ingest_failure.inc();
+        (StatusCode::BAD_REQUEST, format!("Invalid public key: {e}"))
+    })?;
 
-    let hs_url = state
-        .pubky
-        .get_homeserver_of(&user_pk)
-        .await
-        .ok_or_else(|| {
-            (
-                StatusCode::NOT_FOUND,
-                format!("No homeserver found for {public_key}"),
-            )
-        })?;
+    let resolution_timer = state.metrics.homeserver_resolution_seconds.start_timer();
+    let hs_url = state.pubky.get_homeserver_of(&user_pk).await;
+    resolution_timer.observe_duration();
+    let hs_url = hs_url.ok_or_else(|| {
+        state.metrics.ingest_failure.inc();
+        (
+            StatusCode::NOT_FOUND,
+            format!("No homeserver found for {public_key}"),
+        )
+    })?;
 
     let hs_pk = hs_url.to_string();
     // The homeserver URL might be in format like "https://<pk>/" or just the pk
@@ -186,14 +725,16 @@ async fn ingest_user(
 
     info!("Ingesting user {public_key} on homeserver {homeserver_id}");
 
-    let db = state.db.clone();
-    let pk = public_key.clone();
-    let hs = homeserver_id.to_string();
-    tokio::task::spawn_blocking(move || db::add_user(&db, &pk, &hs))
+    state
+        .db
+        .add_user(&public_key, homeserver_id)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(|e| {
+            state.metrics.ingest_failure.inc();
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+        })?;
 
+    state.metrics.ingest_success.inc();
     Ok(StatusCode::CREATED)
 }
 
@@ -219,62 +760,28 @@ async fn get_credits(
     State(state): State<AppState>,
     Path(public_key): Path<String>,
 ) -> Result<Json<CreditsResponse>, StatusCode> {
-    let db = state.db.clone();
-    let config = state.config.clone();
-    let pk = public_key.clone();
-
-    let result = tokio::task::spawn_blocking(move || -> anyhow::Result<CreditsResponse> {
-        let now = crate::pixel::timestamp_micros();
-        let regen_us = config.canvas.credit_regen_seconds as i64 * 1_000_000;
-        let recent = db::count_recent_placements(&db, &pk, now, regen_us)?;
-        let credits = config.canvas.max_credits.saturating_sub(recent);
-
-        let next_credit_in = if credits < config.canvas.max_credits {
-            // Find the oldest placement in the window to know when next credit regens
-            let last = db::get_user_last_placement(&db, &pk)?;
-            match last {
-                Some(_last_placed_at) => {
-                    // Find the earliest placement in the regen window
-                    let cutoff = now - regen_us;
-                    let conn = db.lock().unwrap();
-                    let earliest_in_window: Option<i64> = conn
-                        .query_row(
-                            "SELECT MIN(placed_at) FROM pixel_events WHERE user_pk = ?1 AND placed_at > ?2",
-                            rusqlite::params![pk, cutoff],
-                            |row| row.get(0),
-                        )
-                        .ok();
-                    drop(conn);
-
-                    match earliest_in_window {
-                        Some(earliest) => {
-                            let regen_at = earliest + regen_us;
-                            let remaining_us = (regen_at - now).max(0);
-                            Some((remaining_us / 1_000_000) as u64)
-                        }
-                        None => None,
-                    }
-                }
-                None => None,
-            }
-        } else {
-            None
-        };
-
-        Ok(CreditsResponse {
-            credits,
-            max_credits: config.canvas.max_credits,
-            next_credit_in_seconds: next_credit_in,
-        })
-    })
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    .map_err(|e| {
-        error!("get_credits error: {e:?}");
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    let config = &state.config;
+    let now = crate::pixel::timestamp_micros();
+    let regen_us = config.canvas.credit_regen_seconds as i64 * 1_000_000;
+    let max_tokens = config.canvas.max_credits as f64;
+    let (credits, next_token_us) = state
+        .db
+        .credit_status(&public_key, now, regen_us, max_tokens)
+        .await
+        .map_err(internal("get_credits"))?;
 
-    Ok(Json(result))
+    // No refill pending once the bucket is full.
+    let next_credit_in = if credits < config.canvas.max_credits {
+        Some((next_token_us / 1_000_000) as u64)
+    } else {
+        None
+    };
+
+    Ok(Json(CreditsResponse {
+        credits,
+        max_credits: config.canvas.max_credits,
+        next_credit_in_seconds: next_credit_in,
+    }))
 }
 
 async fn get_profile(
@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{header::AUTHORIZATION, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::config::{AuthConfig, Scope};
+
+/// Per-route middleware state: the issued tokens plus the scope this route needs.
+#[derive(Clone)]
+pub struct AuthContext {
+    config: Option<Arc<AuthConfig>>,
+    scope: Scope,
+}
+
+impl AuthContext {
+    /// Build a context guarding `scope`. `config` is `None` when no `[auth]`
+    /// section is configured, in which case the route stays open.
+    pub fn new(config: Option<Arc<AuthConfig>>, scope: Scope) -> Self {
+        Self { config, scope }
+    }
+}
+
+/// Reject requests lacking a valid, unexpired bearer token carrying the route's
+/// required scope. A missing `[auth]` section disables the check entirely.
+pub async fn authorize(
+    State(ctx): State<AuthContext>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let Some(config) = &ctx.config else {
+        return Ok(next.run(request).await);
+    };
+
+    let token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let now = crate::pixel::timestamp_micros() / 1_000_000;
+    if token_has_scope(config, token, now, ctx.scope) {
+        Ok(next.run(request).await)
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+/// Whether any issued token matches `token`, is unexpired at `now` (unix
+/// seconds), and grants `scope`. The value comparison is constant-time so a
+/// caller can't recover a pre-issued secret byte-by-byte from response timing.
+fn token_has_scope(config: &AuthConfig, token: &str, now: i64, scope: Scope) -> bool {
+    config.tokens.iter().any(|t| {
+        constant_time_eq(t.token.as_bytes(), token.as_bytes())
+            && t.expires_at > now
+            && t.scopes.contains(&scope)
+    })
+}
+
+/// Constant-time byte-slice equality: always scans the whole input rather than
+/// bailing on the first mismatch, so timing doesn't reveal the matching prefix.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut diff = 0u8;
+    for (x, y) in a.iter().zip(b.iter()) {
+        diff |= x ^ y;
+    }
+    diff == 0
+}
@@ -0,0 +1,87 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use s3::creds::Credentials;
+use s3::{Bucket, Region};
+use tracing::{error, info};
+
+use crate::config::BackupConfig;
+use crate::pixel::Palette;
+use crate::store::CanvasStore;
+
+/// Periodically render the canvas to a PNG plus a `PixelState` JSON dump and
+/// upload both to S3-compatible object storage, so the board survives disk loss.
+pub async fn run(
+    config: BackupConfig,
+    palette: Palette,
+    store: Arc<dyn CanvasStore>,
+    mut shutdown_rx: tokio::sync::watch::Receiver<bool>,
+) {
+    let bucket = match build_bucket(&config) {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Backup disabled, failed to configure bucket: {e:?}");
+            return;
+        }
+    };
+
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_seconds.max(1)));
+    info!(
+        "Backup task started, uploading to {}/{} every {}s",
+        config.endpoint, config.bucket, config.interval_seconds
+    );
+
+    loop {
+        tokio::select! {
+            _ = shutdown_rx.changed() => {
+                info!("Backup task shutting down");
+                break;
+            }
+            _ = interval.tick() => {
+                if let Err(e) = upload_snapshot(&bucket, &palette, &store).await {
+                    error!("Backup upload failed: {e:?}");
+                }
+            }
+        }
+    }
+}
+
+fn build_bucket(config: &BackupConfig) -> Result<Box<Bucket>> {
+    let region = Region::Custom {
+        region: config.region.clone(),
+        endpoint: config.endpoint.clone(),
+    };
+    let credentials = Credentials::new(
+        Some(&config.access_key),
+        Some(&config.secret_key),
+        None,
+        None,
+        None,
+    )
+    .context("Invalid S3 credentials")?;
+    let bucket = Bucket::new(&config.bucket, region, credentials)?.with_path_style();
+    Ok(bucket)
+}
+
+async fn upload_snapshot(
+    bucket: &Bucket,
+    palette: &Palette,
+    store: &Arc<dyn CanvasStore>,
+) -> Result<()> {
+    let (width, height) = store.canvas_dims().await?;
+    let pixels = store.canvas_state().await?;
+
+    let png = crate::render::render_png(&pixels, palette, width, height, 1)?;
+    bucket
+        .put_object_with_content_type("canvas.png", &png, "image/png")
+        .await?;
+
+    let json = serde_json::to_vec(&pixels)?;
+    bucket
+        .put_object_with_content_type("canvas.json", &json, "application/json")
+        .await?;
+
+    info!("Backup uploaded ({} cells)", pixels.len());
+    Ok(())
+}
@@ -7,6 +7,76 @@ pub struct Config {
     pub watcher: WatcherConfig,
     pub canvas: CanvasConfig,
     pub database: DatabaseConfig,
+    #[serde(default)]
+    pub events: EventsConfig,
+    #[serde(default)]
+    pub backup: Option<BackupConfig>,
+    #[serde(default)]
+    pub frames: Option<FramesConfig>,
+    #[serde(default)]
+    pub auth: Option<AuthConfig>,
+}
+
+/// Capability tokens gating the write/admin routes. When absent, those routes
+/// stay open (single-operator default); when present, a matching bearer token
+/// with the required scope and a future expiry is required.
+#[derive(Deserialize, Clone, Default)]
+pub struct AuthConfig {
+    #[serde(default)]
+    pub tokens: Vec<TokenConfig>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct TokenConfig {
+    /// The bearer token value presented in the `Authorization` header.
+    pub token: String,
+    /// Unix seconds after which the token is rejected.
+    pub expires_at: i64,
+    /// Scopes this token is allowed to exercise.
+    pub scopes: Vec<Scope>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Ingest,
+    Admin,
+}
+
+/// Periodic backup of the rendered canvas to S3-compatible object storage.
+#[derive(Deserialize, Clone)]
+pub struct BackupConfig {
+    pub endpoint: String,
+    pub bucket: String,
+    pub access_key: String,
+    pub secret_key: String,
+    /// How often to upload a fresh snapshot.
+    pub interval_seconds: u64,
+    /// Region name (defaults to `us-east-1` for generic S3 implementations).
+    #[serde(default = "default_region")]
+    pub region: String,
+}
+
+fn default_region() -> String {
+    "us-east-1".to_string()
+}
+
+/// Periodic PNG frame capture of the live canvas. Each frame is written as
+/// `<timestamp_micros>.png` so the directory forms an ordered timelapse of the
+/// board's growth; a frame is also captured whenever the canvas resizes.
+#[derive(Deserialize, Clone)]
+pub struct FramesConfig {
+    /// Directory the `<timestamp>.png` frames are written to.
+    pub dir: String,
+    /// How often to capture a frame, on top of one per canvas resize.
+    pub interval_seconds: u64,
+    /// Nearest-neighbor upscale factor applied to each frame.
+    #[serde(default = "default_frame_scale")]
+    pub scale: u32,
+}
+
+fn default_frame_scale() -> u32 {
+    8
 }
 
 #[derive(Deserialize, Clone)]
@@ -17,6 +87,22 @@ pub struct ServerConfig {
 #[derive(Deserialize, Clone)]
 pub struct WatcherConfig {
     pub poll_interval_ms: u64,
+    /// Total attempts (the first live one included) a recoverable pixel failure
+    /// gets before it is moved to the dead-letter table.
+    #[serde(default = "default_retry_max_attempts")]
+    pub retry_max_attempts: u32,
+    /// Base delay for exponential backoff between retries, in seconds: attempt
+    /// `n` waits `retry_base_seconds * 2^(n-1)`.
+    #[serde(default = "default_retry_base_seconds")]
+    pub retry_base_seconds: u64,
+}
+
+fn default_retry_max_attempts() -> u32 {
+    5
+}
+
+fn default_retry_base_seconds() -> u64 {
+    10
 }
 
 #[derive(Deserialize, Clone)]
@@ -24,11 +110,46 @@ pub struct CanvasConfig {
     pub initial_size: u32,
     pub max_credits: u32,
     pub credit_regen_seconds: u64,
+    /// Ordered list of `#RRGGBB` colors the board accepts. When unset the board
+    /// uses the built-in 16-color PICO-8 palette; otherwise the length must be a
+    /// power of two up to 256. See [`crate::pixel::Palette`].
+    #[serde(default)]
+    pub palette: Option<Vec<String>>,
+}
+
+impl CanvasConfig {
+    /// Resolve the configured palette, falling back to PICO-8 when unset.
+    pub fn palette(&self) -> anyhow::Result<crate::pixel::Palette> {
+        crate::pixel::Palette::from_config(self.palette.as_ref())
+            .map_err(|e| anyhow::anyhow!("Invalid [canvas] palette: {e}"))
+    }
+}
+
+/// Cross-instance event fan-out. When `redis_url` is unset the server keeps the
+/// single-process broadcast behavior.
+#[derive(Deserialize, Clone, Default)]
+pub struct EventsConfig {
+    #[serde(default)]
+    pub redis_url: Option<String>,
 }
 
 #[derive(Deserialize, Clone)]
 pub struct DatabaseConfig {
     pub path: String,
+    /// Which backend the HTTP query surface runs on. Defaults to SQLite.
+    #[serde(default)]
+    pub backend: StoreBackend,
+    /// Connection string when `backend = "postgres"`.
+    #[serde(default)]
+    pub postgres_url: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreBackend {
+    #[default]
+    Sqlite,
+    Postgres,
 }
 
 impl Config {
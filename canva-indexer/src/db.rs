@@ -1,7 +1,9 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use rusqlite::{params, Connection, OptionalExtension};
 use std::sync::{Arc, Mutex};
 
+use crate::pixel::Palette;
+
 
 pub type Db = Arc<Mutex<Connection>>;
 
@@ -39,17 +41,96 @@ pub fn open(path: &str) -> Result<Db> {
             first_user_pk TEXT NOT NULL,
             placed_at INTEGER NOT NULL,
             was_overwritten INTEGER NOT NULL DEFAULT 0,
+            seq INTEGER NOT NULL DEFAULT 0,
             PRIMARY KEY (x, y)
         );
+        CREATE INDEX IF NOT EXISTS idx_canvas_state_seq ON canvas_state(seq);
+        CREATE INDEX IF NOT EXISTS idx_canvas_state_xy ON canvas_state(x, y);
 
         CREATE TABLE IF NOT EXISTS canvas_resizes (
             width INTEGER NOT NULL,
             height INTEGER NOT NULL,
-            activated_at INTEGER NOT NULL
+            activated_at INTEGER NOT NULL,
+            old_width INTEGER NOT NULL DEFAULT 0,
+            old_height INTEGER NOT NULL DEFAULT 0,
+            seq INTEGER NOT NULL DEFAULT 0
+        );
+
+        CREATE TABLE IF NOT EXISTS canvas_snapshot (
+            id INTEGER PRIMARY KEY CHECK (id = 0),
+            width INTEGER NOT NULL,
+            height INTEGER NOT NULL,
+            packed BLOB NOT NULL,
+            presence BLOB NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS canvas_config (
+            id INTEGER PRIMARY KEY CHECK (id = 0),
+            palette TEXT NOT NULL,
+            bit_depth INTEGER NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS user_credits (
+            user_pk TEXT PRIMARY KEY,
+            tokens REAL NOT NULL,
+            updated_at INTEGER NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS pixel_retry_queue (
+            pixel_id TEXT PRIMARY KEY,
+            user_pk TEXT NOT NULL,
+            uri TEXT NOT NULL,
+            attempt_count INTEGER NOT NULL,
+            next_retry_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_pixel_retry_next
+            ON pixel_retry_queue(next_retry_at);
+
+        CREATE TABLE IF NOT EXISTS pixel_dead_letter (
+            pixel_id TEXT PRIMARY KEY,
+            user_pk TEXT NOT NULL,
+            uri TEXT NOT NULL,
+            reason TEXT NOT NULL,
+            failed_at INTEGER NOT NULL
         );
         ",
     )?;
 
+    // Migration: add the monotonic changefeed sequence to pre-existing DBs.
+    // `ADD COLUMN` errors if it already exists, so the failure is benign.
+    let _ = conn.execute(
+        "ALTER TABLE canvas_state ADD COLUMN seq INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+
+    // Migration: add the resize-sentinel replay columns to pre-existing DBs.
+    // Rows predating this column carry `seq = 0`, so they're simply never
+    // replayed to a re-syncing client (that history is already stale).
+    let _ = conn.execute(
+        "ALTER TABLE canvas_resizes ADD COLUMN old_width INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE canvas_resizes ADD COLUMN old_height INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE canvas_resizes ADD COLUMN seq INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+
+    // Seed sequences for rows migrated in before the column existed: assign each
+    // `seq = 0` cell a distinct positive value by row order. Without this, a
+    // cold-start `changes_since(0)` (which filters `seq > 0`) would omit those
+    // rows from the full board. New cells always get a positive seq on insert,
+    // so this only touches legacy rows and is a no-op on a fresh DB.
+    conn.execute(
+        "UPDATE canvas_state SET seq = (
+             SELECT COUNT(*) FROM canvas_state c2 WHERE c2.rowid <= canvas_state.rowid
+         ) WHERE seq = 0",
+        [],
+    )?;
+
     // Seed initial canvas size if no resizes exist
     let count: i64 = conn.query_row(
         "SELECT COUNT(*) FROM canvas_resizes",
@@ -68,7 +149,7 @@ pub fn open(path: &str) -> Result<Db> {
     Ok(Arc::new(Mutex::new(conn)))
 }
 
-pub fn set_initial_size(db: &Db, size: u32) -> Result<()> {
+pub fn set_initial_size(db: &Db, size: u32, palette: &Palette) -> Result<()> {
     let conn = db.lock().unwrap();
     let count: i64 = conn.query_row(
         "SELECT COUNT(*) FROM canvas_resizes",
@@ -81,6 +162,37 @@ pub fn set_initial_size(db: &Db, size: u32) -> Result<()> {
             params![size, size],
         )?;
     }
+
+    persist_palette(&conn, palette)?;
+    Ok(())
+}
+
+/// Persist the active palette (and its bit-depth) into `canvas_config`, first
+/// checking it can still represent every color already stored. An operator who
+/// shrinks the palette below a color some user already placed would otherwise
+/// leave those cells unrenderable, so that case is rejected at startup.
+fn persist_palette(conn: &Connection, palette: &Palette) -> Result<()> {
+    let max_color: Option<i64> = conn.query_row(
+        "SELECT MAX(color) FROM pixel_events",
+        [],
+        |row| row.get(0),
+    )?;
+    if let Some(max_color) = max_color {
+        if max_color as usize >= palette.len() {
+            bail!(
+                "Configured palette has {} colors but stored events use color index {}",
+                palette.len(),
+                max_color
+            );
+        }
+    }
+
+    let colors = serde_json::to_string(palette.colors())?;
+    conn.execute(
+        "INSERT INTO canvas_config (id, palette, bit_depth) VALUES (0, ?1, ?2)
+         ON CONFLICT(id) DO UPDATE SET palette = ?1, bit_depth = ?2",
+        params![colors, palette.bit_depth()],
+    )?;
     Ok(())
 }
 
@@ -95,6 +207,15 @@ pub fn get_canvas_dimensions(db: &Db) -> Result<(u32, u32)> {
     Ok(dims)
 }
 
+/// Get current canvas size (width of the latest resize).
+///
+/// The board is seeded square and expansion alternates width/height, so the
+/// width is the canonical edge length used by the gameplay APIs.
+pub fn get_canvas_size(db: &Db) -> Result<u32> {
+    let (width, _height) = get_canvas_dimensions(db)?;
+    Ok(width)
+}
+
 /// Get resize history ordered by activated_at ascending (for validation)
 pub fn get_resize_history(db: &Db) -> Result<Vec<(u32, u32, i64)>> {
     let conn = db.lock().unwrap();
@@ -163,22 +284,46 @@ pub fn update_user_cursor(db: &Db, user_pk: &str, cursor: &str) -> Result<()> {
     Ok(())
 }
 
-/// Count recent placements for credit calculation.
-/// Returns how many pixels the user placed within the regen window before `timestamp`.
-pub fn count_recent_placements(
+/// Lazily refill a bucket to `now` and clamp to the burst capacity.
+///
+/// `pub(crate)` so the Postgres store can share the same refill math instead of
+/// re-deriving it against a different SQL dialect.
+pub(crate) fn refill(tokens: f64, updated_at: i64, now: i64, regen_us: i64, max_tokens: f64) -> f64 {
+    let elapsed = (now - updated_at).max(0) as f64;
+    (tokens + elapsed / regen_us as f64).min(max_tokens)
+}
+
+/// Microseconds until the bucket next holds a whole token (0 when one is ready).
+pub(crate) fn next_token_us(tokens: f64, regen_us: i64) -> i64 {
+    if tokens >= 1.0 {
+        0
+    } else {
+        ((1.0 - tokens) * regen_us as f64).ceil() as i64
+    }
+}
+
+/// Read-only view of a user's bucket at `now` without spending: the current
+/// whole-token balance and microseconds until the next refill. Used by the
+/// credits endpoint so the reported balance matches what placement enforces.
+pub fn credit_status(
     db: &Db,
     user_pk: &str,
-    timestamp: i64,
+    now: i64,
     regen_us: i64,
-) -> Result<u32> {
+    max_tokens: f64,
+) -> Result<(u32, i64)> {
     let conn = db.lock().unwrap();
-    let cutoff = timestamp - regen_us;
-    let count: u32 = conn.query_row(
-        "SELECT COUNT(*) FROM pixel_events WHERE user_pk = ?1 AND placed_at > ?2 AND placed_at <= ?3",
-        params![user_pk, cutoff, timestamp],
-        |row| row.get(0),
-    )?;
-    Ok(count)
+    let existing: Option<(f64, i64)> = conn
+        .query_row(
+            "SELECT tokens, updated_at FROM user_credits WHERE user_pk = ?1",
+            params![user_pk],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+
+    let (tokens, updated_at) = existing.unwrap_or((max_tokens, now));
+    let available = refill(tokens, updated_at, now, regen_us, max_tokens);
+    Ok((available.floor() as u32, next_token_us(available, regen_us)))
 }
 
 /// Check if a pixel event ID already exists
@@ -192,9 +337,28 @@ pub fn pixel_event_exists(db: &Db, id: &str) -> Result<bool> {
     Ok(count > 0)
 }
 
-/// Insert a valid pixel event and update canvas state.
-/// Returns (was_new_cell, was_overwritten_by_different_user)
-pub fn insert_pixel(
+/// Outcome of [`spend_credit_and_insert_pixel`].
+pub enum PlacementOutcome {
+    Inserted {
+        was_new: bool,
+        was_overwritten: bool,
+        seq: i64,
+    },
+    InsufficientCredits {
+        next_token_us: i64,
+    },
+}
+
+/// Spend one credit and insert the pixel in a single transaction, so a
+/// transient failure partway through can't leave the user charged for a pixel
+/// that was never placed — a retry re-running this from the top finds the
+/// credit deduction rolled back along with the rest.
+///
+/// Returns `InsufficientCredits` without inserting if the user's bucket is
+/// empty; otherwise `Inserted` with the same shape `insert_pixel` used to
+/// return.
+#[allow(clippy::too_many_arguments)]
+pub fn spend_credit_and_insert_pixel(
     db: &Db,
     id: &str,
     user_pk: &str,
@@ -202,17 +366,55 @@ pub fn insert_pixel(
     y: u32,
     color: u8,
     placed_at: i64,
-) -> Result<(bool, bool)> {
-    let conn = db.lock().unwrap();
+    regen_us: i64,
+    max_tokens: f64,
+) -> Result<PlacementOutcome> {
+    let mut conn = db.lock().unwrap();
+    let tx = conn.transaction()?;
+
+    let existing: Option<(f64, i64)> = tx
+        .query_row(
+            "SELECT tokens, updated_at FROM user_credits WHERE user_pk = ?1",
+            params![user_pk],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let (tokens, updated_at) = existing.unwrap_or((max_tokens, placed_at));
+    let available = refill(tokens, updated_at, placed_at, regen_us, max_tokens);
+    let allowed = available >= 1.0;
+    let remaining = if allowed { available - 1.0 } else { available };
+
+    tx.execute(
+        "INSERT INTO user_credits (user_pk, tokens, updated_at) VALUES (?1, ?2, ?3) \
+         ON CONFLICT(user_pk) DO UPDATE SET tokens = ?2, updated_at = ?3",
+        params![user_pk, remaining, placed_at],
+    )?;
+
+    if !allowed {
+        tx.commit()?;
+        return Ok(PlacementOutcome::InsufficientCredits {
+            next_token_us: next_token_us(remaining, regen_us),
+        });
+    }
 
     // Insert pixel event
-    conn.execute(
+    tx.execute(
         "INSERT INTO pixel_events (id, user_pk, x, y, color, placed_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
         params![id, user_pk, x, y, color, placed_at],
     )?;
 
+    // Assign the next changefeed sequence so stale clients see this write. It
+    // is one past both the pixel and the resize heads, so a pixel placed after
+    // a resize never shares that resize's seq.
+    let next_seq: i64 = tx.query_row(
+        "SELECT MAX(COALESCE((SELECT MAX(seq) FROM canvas_state), 0), \
+         COALESCE((SELECT MAX(seq) FROM canvas_resizes), 0)) + 1",
+        [],
+        |row| row.get(0),
+    )?;
+
     // Check existing canvas state
-    let existing: Option<(String, i32)> = conn
+    let existing: Option<(String, i32)> = tx
         .query_row(
             "SELECT first_user_pk, was_overwritten FROM canvas_state WHERE x = ?1 AND y = ?2",
             params![x, y],
@@ -220,14 +422,14 @@ pub fn insert_pixel(
         )
         .optional()?;
 
-    match existing {
+    let (was_new, was_overwritten) = match existing {
         None => {
             // New cell
-            conn.execute(
-                "INSERT INTO canvas_state (x, y, color, user_pk, first_user_pk, placed_at, was_overwritten) VALUES (?1, ?2, ?3, ?4, ?4, ?5, 0)",
-                params![x, y, color, user_pk, placed_at],
+            tx.execute(
+                "INSERT INTO canvas_state (x, y, color, user_pk, first_user_pk, placed_at, was_overwritten, seq) VALUES (?1, ?2, ?3, ?4, ?4, ?5, 0, ?6)",
+                params![x, y, color, user_pk, placed_at, next_seq],
             )?;
-            Ok((true, false))
+            (true, false)
         }
         Some((first_user, was_overwritten)) => {
             let newly_overwritten = was_overwritten == 0 && first_user != user_pk;
@@ -236,15 +438,255 @@ pub fn insert_pixel(
             } else {
                 0
             };
-            conn.execute(
-                "UPDATE canvas_state SET color = ?1, user_pk = ?2, placed_at = ?3, was_overwritten = ?4 WHERE x = ?5 AND y = ?6",
-                params![color, user_pk, placed_at, ow_val, x, y],
+            // Bump seq on overwrite so clients re-fetch the newest color.
+            tx.execute(
+                "UPDATE canvas_state SET color = ?1, user_pk = ?2, placed_at = ?3, was_overwritten = ?4, seq = ?5 WHERE x = ?6 AND y = ?7",
+                params![color, user_pk, placed_at, ow_val, next_seq, x, y],
+            )?;
+            (false, newly_overwritten)
+        }
+    };
+
+    // Keep the packed snapshot in sync with this write.
+    update_snapshot_cell(&tx, x, y, color)?;
+
+    tx.commit()?;
+    Ok(PlacementOutcome::Inserted {
+        was_new,
+        was_overwritten,
+        seq: next_seq,
+    })
+}
+
+/// Return cells changed since `seq` (ascending), each paired with its own
+/// changefeed sequence, plus the current max sequence, so a client can apply
+/// the diff and record the new cursor in one round-trip.
+pub fn get_changes_since(db: &Db, seq: i64) -> Result<(Vec<(PixelState, i64)>, i64)> {
+    let conn = db.lock().unwrap();
+    let max_seq: i64 = conn.query_row(
+        "SELECT COALESCE(MAX(seq), 0) FROM canvas_state",
+        [],
+        |row| row.get(0),
+    )?;
+    let mut stmt = conn.prepare(
+        "SELECT x, y, color, user_pk, placed_at, seq FROM canvas_state WHERE seq > ?1 ORDER BY seq ASC",
+    )?;
+    let rows = stmt
+        .query_map(params![seq], |row| {
+            Ok((
+                PixelState {
+                    x: row.get(0)?,
+                    y: row.get(1)?,
+                    color: row.get(2)?,
+                    user_pk: row.get(3)?,
+                    placed_at: row.get(4)?,
+                },
+                row.get(5)?,
+            ))
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok((rows, max_seq))
+}
+
+// ---- Packed snapshot --------------------------------------------------------
+//
+// Palettes of up to 16 colors (bit-depth <= 4) pack two pixels per byte (high
+// nibble = even cell index, row-major), matching the original 4-bit layout.
+// Deeper palettes (up to 256 colors) don't fit a nibble, so they pack one byte
+// per pixel instead — simpler than sub-byte bit-packing for depths 5–8 and
+// still a fraction of the JSON encoding. A companion presence bitmap (one bit
+// per cell) records which cells are set. The blobs live in the single-row
+// `canvas_snapshot` table and are maintained incrementally on each placement,
+// turning a full-board read into one small binary fetch.
+
+/// Bytes-per-pixel for the packed buffer at a given palette bit-depth: two
+/// pixels per byte up to 4 bits (16 colors), one byte per pixel beyond that.
+fn bytes_per_pixel(bit_depth: u32) -> PackMode {
+    if bit_depth <= 4 {
+        PackMode::Nibble
+    } else {
+        PackMode::Byte
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PackMode {
+    Nibble,
+    Byte,
+}
+
+fn packed_len(width: u32, height: u32, mode: PackMode) -> usize {
+    let cells = width as usize * height as usize;
+    match mode {
+        PackMode::Nibble => cells.div_ceil(2),
+        PackMode::Byte => cells,
+    }
+}
+
+fn presence_len(width: u32, height: u32) -> usize {
+    (width as usize * height as usize).div_ceil(8)
+}
+
+fn set_cell(packed: &mut [u8], idx: usize, color: u8, mode: PackMode) {
+    match mode {
+        PackMode::Nibble => {
+            let byte = &mut packed[idx / 2];
+            if idx % 2 == 0 {
+                *byte = (*byte & 0x0F) | (color << 4);
+            } else {
+                *byte = (*byte & 0xF0) | (color & 0x0F);
+            }
+        }
+        PackMode::Byte => packed[idx] = color,
+    }
+}
+
+/// The configured palette's bit-depth, as persisted by `persist_palette`.
+/// Defaults to 4 (the original fixed nibble format) if no row exists yet,
+/// e.g. on a database opened before `set_initial_size` has run.
+fn configured_bit_depth(conn: &Connection) -> Result<u32> {
+    let bit_depth: Option<u32> = conn
+        .query_row("SELECT bit_depth FROM canvas_config WHERE id = 0", [], |row| {
+            row.get(0)
+        })
+        .optional()?;
+    Ok(bit_depth.unwrap_or(4))
+}
+
+fn snapshot_pack_mode(conn: &Connection) -> Result<PackMode> {
+    Ok(bytes_per_pixel(configured_bit_depth(conn)?))
+}
+
+fn set_presence_bit(presence: &mut [u8], idx: usize) {
+    presence[idx / 8] |= 1 << (idx % 8);
+}
+
+/// Pack a set of pixel states using the given palette bit-depth (nibble layout
+/// up to 16 colors, one byte per pixel beyond that). Cells out of bounds are
+/// ignored.
+pub fn pack_pixel_states(pixels: &[PixelState], width: u32, height: u32, bit_depth: u32) -> Vec<u8> {
+    let mode = bytes_per_pixel(bit_depth);
+    let mut packed = vec![0u8; packed_len(width, height, mode)];
+    for p in pixels {
+        if p.x < width && p.y < height {
+            set_cell(&mut packed, (p.y * width + p.x) as usize, p.color, mode);
+        }
+    }
+    packed
+}
+
+/// Rebuild both blobs from `canvas_state` and upsert the snapshot row, returning
+/// the freshly packed color buffer. Used on resize, when the board geometry (and
+/// therefore the packing layout) changes.
+fn rebuild_snapshot(conn: &Connection, width: u32, height: u32) -> Result<Vec<u8>> {
+    let mode = snapshot_pack_mode(conn)?;
+    let mut packed = vec![0u8; packed_len(width, height, mode)];
+    let mut presence = vec![0u8; presence_len(width, height)];
+
+    let mut stmt = conn.prepare("SELECT x, y, color FROM canvas_state")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, u32>(0)?, row.get::<_, u32>(1)?, row.get::<_, u8>(2)?))
+    })?;
+    for row in rows {
+        let (x, y, color) = row?;
+        if x < width && y < height {
+            let idx = (y * width + x) as usize;
+            set_cell(&mut packed, idx, color, mode);
+            set_presence_bit(&mut presence, idx);
+        }
+    }
+
+    conn.execute(
+        "INSERT INTO canvas_snapshot (id, width, height, packed, presence) VALUES (0, ?1, ?2, ?3, ?4)
+         ON CONFLICT(id) DO UPDATE SET width = ?1, height = ?2, packed = ?3, presence = ?4",
+        params![width, height, packed, presence],
+    )?;
+    Ok(packed)
+}
+
+/// Apply a single placement to the snapshot blobs, rebuilding first if the row
+/// is missing or its geometry (or packing layout) no longer matches the
+/// current canvas.
+fn update_snapshot_cell(conn: &Connection, x: u32, y: u32, color: u8) -> Result<()> {
+    let (width, height): (u32, u32) = conn.query_row(
+        "SELECT width, height FROM canvas_resizes ORDER BY activated_at DESC LIMIT 1",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let mode = snapshot_pack_mode(conn)?;
+
+    let current: Option<(u32, u32, Vec<u8>, Vec<u8>)> = conn
+        .query_row(
+            "SELECT width, height, packed, presence FROM canvas_snapshot WHERE id = 0",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .optional()?;
+
+    let (mut packed, mut presence) = match current {
+        Some((w, h, packed, presence))
+            if w == width && h == height && packed.len() == packed_len(width, height, mode) =>
+        {
+            (packed, presence)
+        }
+        _ => {
+            rebuild_snapshot(conn, width, height)?;
+            return Ok(());
+        }
+    };
+
+    if x < width && y < height {
+        let idx = (y * width + x) as usize;
+        set_cell(&mut packed, idx, color, mode);
+        set_presence_bit(&mut presence, idx);
+        conn.execute(
+            "UPDATE canvas_snapshot SET packed = ?1, presence = ?2 WHERE id = 0",
+            params![packed, presence],
+        )?;
+    }
+    Ok(())
+}
+
+/// Current board dimensions plus the packed color buffer (nibble- or
+/// byte-per-pixel depending on the palette's bit-depth; see
+/// [`pack_pixel_states`]).
+pub fn get_canvas_packed(db: &Db) -> Result<(u32, u32, Vec<u8>)> {
+    let conn = db.lock().unwrap();
+    let row: Option<(u32, u32, Vec<u8>)> = conn
+        .query_row(
+            "SELECT width, height, packed FROM canvas_snapshot WHERE id = 0",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+
+    match row {
+        Some(r) => Ok(r),
+        None => {
+            // No snapshot yet (fresh DB): build one on demand.
+            let (width, height) = conn.query_row(
+                "SELECT width, height FROM canvas_resizes ORDER BY activated_at DESC LIMIT 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
             )?;
-            Ok((false, newly_overwritten))
+            let packed = rebuild_snapshot(&conn, width, height)?;
+            Ok((width, height, packed))
         }
     }
 }
 
+/// The presence bitmap (one bit per cell) paired with the packed buffer, for
+/// clients that need to distinguish an unset cell from color 0 (black).
+pub fn get_canvas_presence(db: &Db) -> Result<(u32, u32, Vec<u8>)> {
+    let conn = db.lock().unwrap();
+    conn.query_row(
+        "SELECT width, height, presence FROM canvas_snapshot WHERE id = 0",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )
+    .map_err(Into::into)
+}
+
 /// Get canvas fill stats for resize check
 pub fn get_fill_stats(db: &Db) -> Result<(u32, u32)> {
     let conn = db.lock().unwrap();
@@ -261,14 +703,55 @@ pub fn get_fill_stats(db: &Db) -> Result<(u32, u32)> {
     Ok((filled, overwritten))
 }
 
-/// Perform canvas resize
-pub fn resize_canvas(db: &Db, new_width: u32, new_height: u32, activated_at: i64) -> Result<()> {
+/// Perform canvas resize, recording the prior dimensions and a fresh
+/// changefeed `seq` (one past the current pixel head) so the resize sorts
+/// strictly after every pixel placed before it and a lagged SSE client can
+/// replay it later via `resizes_since`. Returns the assigned seq.
+pub fn resize_canvas(
+    db: &Db,
+    old_width: u32,
+    old_height: u32,
+    new_width: u32,
+    new_height: u32,
+    activated_at: i64,
+) -> Result<i64> {
     let conn = db.lock().unwrap();
+    let seq: i64 = conn.query_row(
+        "SELECT COALESCE(MAX(seq), 0) + 1 FROM canvas_state",
+        [],
+        |row| row.get(0),
+    )?;
     conn.execute(
-        "INSERT INTO canvas_resizes (width, height, activated_at) VALUES (?1, ?2, ?3)",
-        params![new_width, new_height, activated_at],
+        "INSERT INTO canvas_resizes (width, height, activated_at, old_width, old_height, seq) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![new_width, new_height, activated_at, old_width, old_height, seq],
     )?;
-    Ok(())
+    // The packing layout depends on the board width, so rebuild the snapshot.
+    rebuild_snapshot(&conn, new_width, new_height)?;
+    Ok(seq)
+}
+
+/// Resizes activated with a changefeed `seq` greater than `seq`, ascending —
+/// the resize-sentinel counterpart to `get_changes_since`, so a re-syncing SSE
+/// client learns about canvas growth it missed while disconnected.
+pub fn get_resizes_since(db: &Db, seq: i64) -> Result<Vec<(u32, u32, u32, u32, i64)>> {
+    let conn = db.lock().unwrap();
+    let mut stmt = conn.prepare(
+        "SELECT old_width, old_height, width, height, seq FROM canvas_resizes \
+         WHERE seq > ?1 ORDER BY seq ASC",
+    )?;
+    let rows = stmt
+        .query_map(params![seq], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+            ))
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(rows)
 }
 
 /// Get full canvas state for API response
@@ -291,6 +774,81 @@ pub fn get_canvas_state(db: &Db) -> Result<Vec<PixelState>> {
     Ok(rows)
 }
 
+/// Get the filled cells inside an inclusive `(x0, y0)`–`(x1, y1)` rectangle.
+///
+/// Backed by the `idx_canvas_state_xy` spatial index so a client can fetch just
+/// the viewport it's displaying instead of the whole board — the read cost
+/// scales with the tile, not the canvas.
+pub fn get_region(db: &Db, x0: u32, y0: u32, x1: u32, y1: u32) -> Result<Vec<PixelState>> {
+    let conn = db.lock().unwrap();
+    let mut stmt = conn.prepare(
+        "SELECT x, y, color, user_pk, placed_at FROM canvas_state \
+         WHERE x >= ?1 AND x <= ?3 AND y >= ?2 AND y <= ?4",
+    )?;
+    let rows = stmt
+        .query_map(params![x0, y0, x1, y1], |row| {
+            Ok(PixelState {
+                x: row.get(0)?,
+                y: row.get(1)?,
+                color: row.get(2)?,
+                user_pk: row.get(3)?,
+                placed_at: row.get(4)?,
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Packed color buffer for a region, clipped to the `(x0, y0)`–`(x1, y1)`
+/// rectangle and addressed in tile-local coordinates.
+///
+/// Returns `(width, height, packed)` using the same layout as
+/// [`get_canvas_packed`] (nibble- or byte-per-pixel depending on the
+/// configured palette's bit-depth), so a client can cache and decode each tile
+/// exactly like a full-board snapshot.
+pub fn get_region_packed(db: &Db, x0: u32, y0: u32, x1: u32, y1: u32) -> Result<(u32, u32, Vec<u8>)> {
+    let width = x1.saturating_sub(x0) + 1;
+    let height = y1.saturating_sub(y0) + 1;
+    let pixels = get_region(db, x0, y0, x1, y1)?;
+    let bit_depth = {
+        let conn = db.lock().unwrap();
+        configured_bit_depth(&conn)?
+    };
+    Ok((width, height, pack_region(&pixels, x0, y0, width, height, bit_depth)))
+}
+
+/// Pack region pixels into tile-local coordinates (each cell offset by the
+/// tile origin `(x0, y0)`) using the given palette bit-depth. Cells outside
+/// the tile are skipped.
+pub fn pack_region(
+    pixels: &[PixelState],
+    x0: u32,
+    y0: u32,
+    width: u32,
+    height: u32,
+    bit_depth: u32,
+) -> Vec<u8> {
+    let mode = bytes_per_pixel(bit_depth);
+    let mut packed = vec![0u8; packed_len(width, height, mode)];
+    for p in pixels {
+        if p.x >= x0 && p.y >= y0 && p.x - x0 < width && p.y - y0 < height {
+            set_cell(&mut packed, ((p.y - y0) * width + (p.x - x0)) as usize, p.color, mode);
+        }
+    }
+    packed
+}
+
+/// Inclusive pixel bounds of the `(tx, ty)` tile for a fixed `tile_size` grid.
+///
+/// Frontends pan/zoom by requesting whole tiles (e.g. 64×64) and caching each
+/// independently; this maps a tile coordinate to the rectangle passed to
+/// [`get_region`] / [`get_region_packed`].
+pub fn tile_bounds(tile_size: u32, tx: u32, ty: u32) -> (u32, u32, u32, u32) {
+    let x0 = tx * tile_size;
+    let y0 = ty * tile_size;
+    (x0, y0, x0 + tile_size - 1, y0 + tile_size - 1)
+}
+
 /// Get info for a single pixel
 pub fn get_pixel_info(db: &Db, x: u32, y: u32) -> Result<Option<PixelInfo>> {
     let conn = db.lock().unwrap();
@@ -336,6 +894,96 @@ pub fn get_pixel_info(db: &Db, x: u32, y: u32) -> Result<Option<PixelInfo>> {
     }))
 }
 
+/// Get the most recent pixel placements, newest first, for the activity feed.
+pub fn get_recent_events(db: &Db, limit: u32) -> Result<Vec<PixelEvent>> {
+    let conn = db.lock().unwrap();
+    let mut stmt = conn.prepare(
+        "SELECT id, user_pk, x, y, color, placed_at FROM pixel_events \
+         ORDER BY placed_at DESC LIMIT ?1",
+    )?;
+    let rows = stmt
+        .query_map(params![limit], |row| {
+            Ok(PixelEvent {
+                id: row.get(0)?,
+                user_pk: row.get(1)?,
+                x: row.get(2)?,
+                y: row.get(3)?,
+                color: row.get(4)?,
+                placed_at: row.get(5)?,
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Active canvas dimensions at `timestamp`: the latest resize activated at or
+/// before that instant, or `(0, 0)` if `timestamp` predates the first resize
+/// (i.e. the canvas did not exist yet, so it was empty).
+fn active_size_at(conn: &Connection, timestamp: i64) -> Result<(u32, u32)> {
+    let dims = conn.query_row(
+        "SELECT width, height FROM canvas_resizes WHERE activated_at <= ?1 \
+         ORDER BY activated_at DESC LIMIT 1",
+        params![timestamp],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    );
+    match dims {
+        Ok(dims) => Ok(dims),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok((0, 0)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Reconstruct the canvas as it looked at `timestamp` by replaying history:
+/// for each `(x, y)` keep the most recent event at or before the cutoff, then
+/// drop cells that lay outside the canvas bounds active at that moment.
+pub fn reconstruct_at(db: &Db, timestamp: i64) -> Result<Vec<PixelState>> {
+    let conn = db.lock().unwrap();
+    let (width, height) = active_size_at(&conn, timestamp)?;
+
+    // SQLite's bare-column/MAX() rule makes the non-aggregated columns come from
+    // the row holding the maximum `placed_at`, i.e. the color current at cutoff.
+    let mut stmt = conn.prepare(
+        "SELECT x, y, color, user_pk, MAX(placed_at) AS placed_at \
+         FROM pixel_events WHERE placed_at <= ?1 GROUP BY x, y",
+    )?;
+    let rows = stmt
+        .query_map(params![timestamp], |row| {
+            Ok(PixelState {
+                x: row.get(0)?,
+                y: row.get(1)?,
+                color: row.get(2)?,
+                user_pk: row.get(3)?,
+                placed_at: row.get(4)?,
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(rows
+        .into_iter()
+        .filter(|p| p.x < width && p.y < height)
+        .collect())
+}
+
+/// Reconstruct a series of snapshots from `from` to `to` (inclusive) every
+/// `step_us` microseconds, suitable for assembling a timelapse animation.
+pub fn get_history_frames(
+    db: &Db,
+    from: i64,
+    to: i64,
+    step_us: i64,
+) -> Result<Vec<(i64, Vec<PixelState>)>> {
+    if step_us <= 0 {
+        return Err(anyhow::anyhow!("step_us must be positive"));
+    }
+    let mut frames = Vec::new();
+    let mut t = from;
+    while t <= to {
+        frames.push((t, reconstruct_at(db, t)?));
+        t += step_us;
+    }
+    Ok(frames)
+}
+
 /// Get a user's last placement timestamp
 pub fn get_user_last_placement(db: &Db, user_pk: &str) -> Result<Option<i64>> {
     let conn = db.lock().unwrap();
@@ -348,7 +996,82 @@ pub fn get_user_last_placement(db: &Db, user_pk: &str) -> Result<Option<i64>> {
     .map_err(Into::into)
 }
 
-#[derive(serde::Serialize, Clone, Debug)]
+/// Enqueue (or reschedule) a pixel event that failed with a recoverable error,
+/// recording its next attempt number and when it becomes due again.
+pub fn enqueue_retry(
+    db: &Db,
+    pixel_id: &str,
+    user_pk: &str,
+    uri: &str,
+    attempt_count: u32,
+    next_retry_at: i64,
+) -> Result<()> {
+    let conn = db.lock().unwrap();
+    conn.execute(
+        "INSERT INTO pixel_retry_queue (pixel_id, user_pk, uri, attempt_count, next_retry_at) \
+         VALUES (?1, ?2, ?3, ?4, ?5) \
+         ON CONFLICT(pixel_id) DO UPDATE SET attempt_count = ?4, next_retry_at = ?5",
+        params![pixel_id, user_pk, uri, attempt_count, next_retry_at],
+    )?;
+    Ok(())
+}
+
+/// Retry-queue entries due for another attempt at or before `now`.
+pub fn due_retries(db: &Db, now: i64) -> Result<Vec<RetryEntry>> {
+    let conn = db.lock().unwrap();
+    let mut stmt = conn.prepare(
+        "SELECT pixel_id, user_pk, uri, attempt_count FROM pixel_retry_queue \
+         WHERE next_retry_at <= ?1 ORDER BY next_retry_at",
+    )?;
+    let rows = stmt
+        .query_map(params![now], |row| {
+            Ok(RetryEntry {
+                pixel_id: row.get(0)?,
+                user_pk: row.get(1)?,
+                uri: row.get(2)?,
+                attempt_count: row.get(3)?,
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Drop a pixel from the retry queue once it succeeds or is dead-lettered.
+pub fn remove_retry(db: &Db, pixel_id: &str) -> Result<()> {
+    let conn = db.lock().unwrap();
+    conn.execute(
+        "DELETE FROM pixel_retry_queue WHERE pixel_id = ?1",
+        params![pixel_id],
+    )?;
+    Ok(())
+}
+
+/// Move a pixel to the dead-letter table with a human-readable reason, removing
+/// it from the retry queue. Used for permanent failures and for transient ones
+/// that exhaust the retry cap.
+pub fn dead_letter(
+    db: &Db,
+    pixel_id: &str,
+    user_pk: &str,
+    uri: &str,
+    reason: &str,
+    failed_at: i64,
+) -> Result<()> {
+    let conn = db.lock().unwrap();
+    conn.execute(
+        "INSERT INTO pixel_dead_letter (pixel_id, user_pk, uri, reason, failed_at) \
+         VALUES (?1, ?2, ?3, ?4, ?5) \
+         ON CONFLICT(pixel_id) DO UPDATE SET reason = ?4, failed_at = ?5",
+        params![pixel_id, user_pk, uri, reason, failed_at],
+    )?;
+    conn.execute(
+        "DELETE FROM pixel_retry_queue WHERE pixel_id = ?1",
+        params![pixel_id],
+    )?;
+    Ok(())
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub struct PixelState {
     pub x: u32,
     pub y: u32,
@@ -357,6 +1080,25 @@ pub struct PixelState {
     pub placed_at: i64,
 }
 
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct PixelEvent {
+    pub id: String,
+    pub user_pk: String,
+    pub x: u32,
+    pub y: u32,
+    pub color: u8,
+    pub placed_at: i64,
+}
+
+/// A pixel event awaiting another ingestion attempt.
+#[derive(Clone, Debug)]
+pub struct RetryEntry {
+    pub pixel_id: String,
+    pub user_pk: String,
+    pub uri: String,
+    pub attempt_count: u32,
+}
+
 #[derive(serde::Serialize, Clone, Debug)]
 pub struct PixelInfo {
     pub current: PixelState,
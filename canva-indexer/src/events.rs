@@ -0,0 +1,161 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::watcher::SseEvent;
+
+/// Fan-out hub for `SseEvent`s.
+///
+/// Wraps the in-process `broadcast::Sender` that `sse_events` subscribes to and,
+/// when a `[events] redis_url` is configured, mirrors every locally-produced
+/// event onto a Redis channel so other API instances can re-inject it into their
+/// own local channel. Each envelope carries this instance's UUID so an instance
+/// never re-broadcasts its own events back to its clients.
+#[derive(Clone)]
+pub struct Events {
+    instance_id: Uuid,
+    local: broadcast::Sender<SseEvent>,
+    redis: Option<redis::Client>,
+    channel: String,
+}
+
+/// Wire envelope published to Redis: the originating instance plus the event.
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    instance: Uuid,
+    event: SseEvent,
+}
+
+const REDIS_CHANNEL: &str = "pubky-canva:events";
+
+/// Pluggable fan-out bus for `SseEvent`s, decoupling producers (the watcher's
+/// `process_pixel_event`/`check_resize`) from how events reach subscribers.
+///
+/// [`Events`] is the default implementation: a single in-process
+/// `broadcast::Sender` (the local bus) that, when a `[events] redis_url` is
+/// configured, also mirrors every event onto a Redis pub/sub channel so other
+/// server instances share placements. De-duplication across instances relies on
+/// the existing `pixel_event_exists` check, so a placement ingested by two
+/// instances is still broadcast once.
+pub trait EventBus: Send + Sync {
+    /// Publish a locally-produced event to subscribers and any configured peers.
+    fn publish(&self, event: SseEvent);
+
+    /// Subscribe a new client to the local delivery channel.
+    fn subscribe(&self) -> broadcast::Receiver<SseEvent>;
+}
+
+impl EventBus for Events {
+    fn publish(&self, event: SseEvent) {
+        self.send(event);
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<SseEvent> {
+        Events::subscribe(self)
+    }
+}
+
+impl Events {
+    /// Build a hub backed by `local`, optionally bridged to Redis.
+    pub fn new(local: broadcast::Sender<SseEvent>, redis_url: Option<&str>) -> anyhow::Result<Self> {
+        let redis = match redis_url {
+            Some(url) => Some(redis::Client::open(url)?),
+            None => None,
+        };
+        Ok(Self {
+            instance_id: Uuid::new_v4(),
+            local,
+            redis,
+            channel: REDIS_CHANNEL.to_string(),
+        })
+    }
+
+    /// Subscribe a new client to the local broadcast channel.
+    pub fn subscribe(&self) -> broadcast::Receiver<SseEvent> {
+        self.local.subscribe()
+    }
+
+    /// Publish an event produced by this instance: deliver it locally and, if a
+    /// Redis bridge is present, publish it to the shared channel for peers.
+    pub fn send(&self, event: SseEvent) {
+        let _ = self.local.send(event.clone());
+
+        if let Some(client) = &self.redis {
+            let client = client.clone();
+            let channel = self.channel.clone();
+            let envelope = Envelope {
+                instance: self.instance_id,
+                event,
+            };
+            tokio::spawn(async move {
+                if let Err(e) = publish(&client, &channel, &envelope).await {
+                    warn!("Failed to publish event to Redis: {e}");
+                }
+            });
+        }
+    }
+
+    /// Re-inject a peer event into the local channel only — never back onto Redis,
+    /// which would otherwise amplify every placement across the fleet forever.
+    fn inject(&self, event: SseEvent) {
+        let _ = self.local.send(event);
+    }
+
+    /// Spawn the Redis SUBSCRIBE loop that re-injects peer events. No-op (and no
+    /// task) when Redis isn't configured, preserving single-process behavior.
+    pub fn spawn_bridge(self: &Arc<Self>) {
+        let Some(client) = self.redis.clone() else {
+            return;
+        };
+        let this = self.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = this.run_subscribe(&client).await {
+                    error!("Redis subscribe loop error: {e}; reconnecting");
+                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                }
+            }
+        });
+        info!("Redis event bridge active on channel {}", self.channel);
+    }
+
+    async fn run_subscribe(&self, client: &redis::Client) -> anyhow::Result<()> {
+        let mut pubsub = client.get_async_pubsub().await?;
+        pubsub.subscribe(&self.channel).await?;
+        let mut stream = pubsub.on_message();
+        use tokio_stream::StreamExt;
+        while let Some(msg) = stream.next().await {
+            let payload: String = msg.get_payload()?;
+            let envelope: Envelope = match serde_json::from_str(&payload) {
+                Ok(env) => env,
+                Err(e) => {
+                    warn!("Dropping malformed Redis event: {e}");
+                    continue;
+                }
+            };
+            // Ignore our own events; they were already delivered locally.
+            if envelope.instance != self.instance_id {
+                self.inject(envelope.event);
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn publish(
+    client: &redis::Client,
+    channel: &str,
+    envelope: &Envelope,
+) -> anyhow::Result<()> {
+    let mut conn = client.get_multiplexed_async_connection().await?;
+    let payload = serde_json::to_string(envelope)?;
+    redis::cmd("PUBLISH")
+        .arg(channel)
+        .arg(payload)
+        .query_async::<()>(&mut conn)
+        .await?;
+    Ok(())
+}
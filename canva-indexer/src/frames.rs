@@ -0,0 +1,65 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tracing::{error, info};
+
+use crate::config::FramesConfig;
+use crate::pixel::{self, Palette};
+use crate::store::CanvasStore;
+
+/// Periodically render the live canvas to a PNG frame on disk, building an
+/// ordered sequence that can be stitched into a timelapse of the board's growth.
+/// A frame is also captured on every resize by the watcher (see `check_resize`).
+/// Reads through the configured `CanvasStore` so a Postgres-backed deployment
+/// captures the real board instead of the (empty) local SQLite file.
+pub async fn run(
+    config: FramesConfig,
+    palette: Palette,
+    store: Arc<dyn CanvasStore>,
+    mut shutdown_rx: tokio::sync::watch::Receiver<bool>,
+) {
+    if let Err(e) = std::fs::create_dir_all(&config.dir) {
+        error!("Frame capture disabled, cannot create {}: {e:?}", config.dir);
+        return;
+    }
+
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_seconds.max(1)));
+    info!(
+        "Frame capture started, writing to {} every {}s",
+        config.dir, config.interval_seconds
+    );
+
+    loop {
+        tokio::select! {
+            _ = shutdown_rx.changed() => {
+                info!("Frame capture shutting down");
+                break;
+            }
+            _ = interval.tick() => {
+                match capture(&config, &palette, &store).await {
+                    Ok(path) => info!("Wrote frame {}", path.display()),
+                    Err(e) => error!("Frame capture failed: {e:?}"),
+                }
+            }
+        }
+    }
+}
+
+/// Render the current canvas and write it as a PNG keyed by the capture time,
+/// returning the frame's path. Shared by the periodic task and the watcher's
+/// resize hook so both land in the same timelapse directory.
+pub async fn capture(
+    config: &FramesConfig,
+    palette: &Palette,
+    store: &Arc<dyn CanvasStore>,
+) -> Result<PathBuf> {
+    let (width, height) = store.canvas_dims().await?;
+    let pixels = store.canvas_state().await?;
+
+    let png = crate::render::render_png(&pixels, palette, width, height, config.scale)?;
+    let path = Path::new(&config.dir).join(format!("{}.png", pixel::timestamp_micros()));
+    std::fs::write(&path, &png).with_context(|| format!("writing frame {}", path.display()))?;
+    Ok(path)
+}
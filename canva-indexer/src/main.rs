@@ -1,7 +1,14 @@
 mod api;
+mod auth;
+mod backup;
 mod config;
 mod db;
+mod events;
+mod frames;
+mod metrics;
 mod pixel;
+mod render;
+mod store;
 mod watcher;
 
 use anyhow::Result;
@@ -22,27 +29,67 @@ async fn main() -> Result<()> {
     info!("Loaded config from {config_path}");
 
     // Open database
+    let palette = config.canvas.palette()?;
     let database = db::open(&config.database.path)?;
-    db::set_initial_size(&database, config.canvas.initial_size)?;
-    info!("Database opened at {}", config.database.path);
+    db::set_initial_size(&database, config.canvas.initial_size, &palette)?;
+    info!(
+        "Database opened at {} ({}-color palette, {}-bit)",
+        config.database.path,
+        palette.len(),
+        palette.bit_depth()
+    );
+
+    // Query-surface store backend (SQLite by default, Postgres when configured)
+    let store = store::from_config(&config.database, &config.canvas, database.clone()).await?;
+    info!("Store backend: {:?}", config.database.backend);
 
     // Initialize Pubky client (mainnet)
     let client = PubkyHttpClient::new()?;
     let pubky = Arc::new(Pubky::with_client(client));
     info!("Pubky client initialized");
 
-    // SSE broadcast channel
+    // SSE broadcast channel, optionally bridged across instances via Redis
     let (sse_tx, _) = broadcast::channel::<watcher::SseEvent>(256);
+    let events = Arc::new(events::Events::new(
+        sse_tx,
+        config.events.redis_url.as_deref(),
+    )?);
+    events.spawn_bridge();
+    let events: Arc<dyn events::EventBus> = events;
+
+    // Metrics registry
+    let metrics = Arc::new(metrics::Metrics::new()?);
 
     // Shutdown signal
     let (shutdown_tx, shutdown_rx) = watch::channel(false);
 
+    // Periodic S3-compatible backups of the rendered canvas
+    if let Some(backup_config) = config.backup.clone() {
+        tokio::spawn(backup::run(
+            backup_config,
+            palette.clone(),
+            store.clone(),
+            shutdown_rx.clone(),
+        ));
+    }
+
+    // Periodic PNG frame capture for timelapse archives
+    if let Some(frames_config) = config.frames.clone() {
+        tokio::spawn(frames::run(
+            frames_config,
+            palette.clone(),
+            store.clone(),
+            shutdown_rx.clone(),
+        ));
+    }
+
     // Build API
     let app_state = api::AppState {
-        db: database.clone(),
+        db: store,
         pubky: pubky.clone(),
         config: config.clone(),
-        sse_tx: sse_tx.clone(),
+        events: events.clone(),
+        metrics: metrics.clone(),
     };
     let app = api::router(app_state);
 
@@ -63,10 +110,11 @@ async fn main() -> Result<()> {
     });
 
     let watcher_handle = tokio::spawn(watcher::run(
-        database,
+        store.clone(),
         pubky,
         config,
-        sse_tx,
+        events,
+        metrics,
         shutdown_rx,
     ));
 
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use prometheus::{
+    Histogram, HistogramOpts, IntCounter, IntGauge, Opts, Registry, TextEncoder,
+};
+
+/// Server-internal instrumentation surfaced at `GET /metrics`.
+///
+/// Handlers and the watcher record into the instruments directly; the scrape
+/// handler refreshes the canvas gauges from the store before encoding, so the
+/// registry never needs global state.
+pub struct Metrics {
+    registry: Registry,
+    pub pixels_placed: IntCounter,
+    pub ingest_success: IntCounter,
+    pub ingest_failure: IntCounter,
+    pub sse_subscribers: IntGauge,
+    pub pixels_filled: IntGauge,
+    pub pixels_overwritten: IntGauge,
+    pub homeserver_resolution_seconds: Histogram,
+    pub db_query_seconds: Histogram,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self> {
+        let registry = Registry::new();
+
+        let pixels_placed =
+            IntCounter::with_opts(Opts::new("canva_pixels_placed_total", "Pixels placed"))?;
+        let ingest_success = IntCounter::with_opts(Opts::new(
+            "canva_ingest_success_total",
+            "Successful user ingestions",
+        ))?;
+        let ingest_failure = IntCounter::with_opts(Opts::new(
+            "canva_ingest_failure_total",
+            "Failed user ingestions",
+        ))?;
+        let sse_subscribers = IntGauge::with_opts(Opts::new(
+            "canva_sse_subscribers",
+            "Currently connected SSE subscribers",
+        ))?;
+        let pixels_filled =
+            IntGauge::with_opts(Opts::new("canva_pixels_filled", "Filled canvas cells"))?;
+        let pixels_overwritten = IntGauge::with_opts(Opts::new(
+            "canva_pixels_overwritten",
+            "Overwritten canvas cells",
+        ))?;
+        let homeserver_resolution_seconds = Histogram::with_opts(HistogramOpts::new(
+            "canva_homeserver_resolution_seconds",
+            "Latency of homeserver resolution via Pkarr/DHT",
+        ))?;
+        let db_query_seconds = Histogram::with_opts(HistogramOpts::new(
+            "canva_db_query_seconds",
+            "Duration of store queries",
+        ))?;
+
+        registry.register(Box::new(pixels_placed.clone()))?;
+        registry.register(Box::new(ingest_success.clone()))?;
+        registry.register(Box::new(ingest_failure.clone()))?;
+        registry.register(Box::new(sse_subscribers.clone()))?;
+        registry.register(Box::new(pixels_filled.clone()))?;
+        registry.register(Box::new(pixels_overwritten.clone()))?;
+        registry.register(Box::new(homeserver_resolution_seconds.clone()))?;
+        registry.register(Box::new(db_query_seconds.clone()))?;
+
+        Ok(Self {
+            registry,
+            pixels_placed,
+            ingest_success,
+            ingest_failure,
+            sse_subscribers,
+            pixels_filled,
+            pixels_overwritten,
+            homeserver_resolution_seconds,
+            db_query_seconds,
+        })
+    }
+
+    /// Encode the registry in Prometheus text-exposition format.
+    pub fn encode(&self) -> Result<String> {
+        let encoder = TextEncoder::new();
+        let metric_families = self.registry.gather();
+        Ok(encoder.encode_to_string(&metric_families)?)
+    }
+}
+
+/// RAII guard that keeps the SSE-subscriber gauge in sync: increments on
+/// creation, decrements when the stream is dropped.
+pub struct SubscriberGuard {
+    gauge: IntGauge,
+}
+
+impl SubscriberGuard {
+    pub fn new(gauge: IntGauge) -> Self {
+        gauge.inc();
+        Self { gauge }
+    }
+}
+
+impl Drop for SubscriberGuard {
+    fn drop(&mut self) {
+        self.gauge.dec();
+    }
+}
@@ -20,6 +20,63 @@ pub const PICO8_PALETTE: [&str; 16] = [
     "#FFCCAA", // 15: Peach
 ];
 
+/// An ordered set of palette colors (hex strings like `#RRGGBB`).
+///
+/// The palette length must be a power of two up to 256 so a color index packs
+/// into a fixed bit-depth (`log2(len)` bits), matching the packed-snapshot
+/// layout. Defaults to the 16-color PICO-8 set when an operator doesn't
+/// configure their own.
+#[derive(Debug, Clone)]
+pub struct Palette {
+    colors: Vec<String>,
+}
+
+impl Palette {
+    /// The built-in 16-color PICO-8 palette.
+    pub fn pico8() -> Self {
+        Self {
+            colors: PICO8_PALETTE.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    /// Build a palette from configured hex colors, validating its shape.
+    ///
+    /// `None` (no `[canvas] palette` configured) yields the PICO-8 default.
+    pub fn from_config(colors: Option<&Vec<String>>) -> Result<Self, String> {
+        match colors {
+            None => Ok(Self::pico8()),
+            Some(colors) => {
+                let len = colors.len();
+                if len == 0 || len > 256 || !len.is_power_of_two() {
+                    return Err(format!(
+                        "Palette length {len} must be a power of two between 1 and 256"
+                    ));
+                }
+                Ok(Self {
+                    colors: colors.clone(),
+                })
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.colors.is_empty()
+    }
+
+    /// Number of bits needed to index this palette (`log2(len)`).
+    pub fn bit_depth(&self) -> u32 {
+        self.colors.len().trailing_zeros()
+    }
+
+    pub fn colors(&self) -> &[String] {
+        &self.colors
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct CanvaPixel {
     pub x: u32,
@@ -30,28 +87,31 @@ pub struct CanvaPixel {
 impl CanvaPixel {
     pub fn validate(
         &self,
-        canvas_size: u32,
-        resize_history: &[(u32, i64)],
+        palette_len: usize,
+        canvas_width: u32,
+        canvas_height: u32,
+        resize_history: &[(u32, u32, i64)],
         timestamp: i64,
     ) -> Result<(), String> {
-        if self.color > 15 {
+        if self.color as usize >= palette_len {
             return Err(format!(
-                "Invalid color index: {} (must be 0-15)",
-                self.color
+                "Invalid color index: {} (must be 0-{})",
+                self.color,
+                palette_len - 1
             ));
         }
 
-        if self.x >= canvas_size || self.y >= canvas_size {
+        if self.x >= canvas_width || self.y >= canvas_height {
             return Err(format!(
-                "Coordinates ({}, {}) out of bounds for canvas size {}",
-                self.x, self.y, canvas_size
+                "Coordinates ({}, {}) out of bounds for canvas {}x{}",
+                self.x, self.y, canvas_width, canvas_height
             ));
         }
 
-        // Anti-cheat: ensure pixel wasn't pre-placed before the canvas expanded to include it
-        let required_size = self.x.max(self.y) + 1;
-        for &(size, activated_at) in resize_history {
-            if size >= required_size {
+        // Anti-cheat: ensure pixel wasn't pre-placed before the canvas expanded
+        // to include it.
+        for &(width, height, activated_at) in resize_history {
+            if width > self.x && height > self.y {
                 if timestamp < activated_at {
                     return Err(format!(
                         "Pixel at ({}, {}) placed before canvas expanded to include it",
@@ -0,0 +1,124 @@
+use anyhow::{anyhow, Result};
+use image::{ImageFormat, RgbaImage};
+use std::io::Cursor;
+
+use crate::db::PixelState;
+use crate::pixel::Palette;
+
+/// Fully transparent background: the default for unset cells.
+pub const TRANSPARENT: [u8; 4] = [0, 0, 0, 0];
+
+/// Render a canvas state to a PNG with transparent unset cells.
+///
+/// Thin wrapper over [`render_png_bg`]; `scale` upscales the board with
+/// nearest-neighbor sampling (a `scale` of 0 is treated as 1) so a tiny board
+/// still produces a shareable image.
+pub fn render_png(
+    pixels: &[PixelState],
+    palette: &Palette,
+    width: u32,
+    height: u32,
+    scale: u32,
+) -> Result<Vec<u8>> {
+    render_png_bg(pixels, palette, width, height, scale, TRANSPARENT)
+}
+
+/// Render a canvas state to a PNG, filling unset cells with `background`.
+///
+/// Each cell's palette index is looked up in `palette` and written as an opaque
+/// RGBA pixel.
+pub fn render_png_bg(
+    pixels: &[PixelState],
+    palette: &Palette,
+    width: u32,
+    height: u32,
+    scale: u32,
+    background: [u8; 4],
+) -> Result<Vec<u8>> {
+    let img = render_image(pixels, palette, width, height, scale, background)?;
+    let mut buf = Vec::new();
+    img.write_to(&mut Cursor::new(&mut buf), ImageFormat::Png)?;
+    Ok(buf)
+}
+
+/// Render a timelapse: each reconstructed frame (as produced by
+/// `db::get_history_frames`) becomes a PNG keyed by its timestamp.
+pub fn render_frames(
+    frames: &[(i64, Vec<PixelState>)],
+    palette: &Palette,
+    width: u32,
+    height: u32,
+    scale: u32,
+    background: [u8; 4],
+) -> Result<Vec<(i64, Vec<u8>)>> {
+    let rgb = parse_palette(palette)?;
+    frames
+        .iter()
+        .map(|(ts, pixels)| {
+            let img = render_image_rgb(pixels, &rgb, width, height, scale, background)?;
+            let mut buf = Vec::new();
+            img.write_to(&mut Cursor::new(&mut buf), ImageFormat::Png)?;
+            Ok((*ts, buf))
+        })
+        .collect()
+}
+
+fn render_image(
+    pixels: &[PixelState],
+    palette: &Palette,
+    width: u32,
+    height: u32,
+    scale: u32,
+    background: [u8; 4],
+) -> Result<RgbaImage> {
+    let rgb = parse_palette(palette)?;
+    render_image_rgb(pixels, &rgb, width, height, scale, background)
+}
+
+fn render_image_rgb(
+    pixels: &[PixelState],
+    palette: &[[u8; 3]],
+    width: u32,
+    height: u32,
+    scale: u32,
+    background: [u8; 4],
+) -> Result<RgbaImage> {
+    let scale = scale.max(1);
+
+    let mut img = RgbaImage::from_pixel(width * scale, height * scale, image::Rgba(background));
+    for pixel in pixels {
+        if pixel.x >= width || pixel.y >= height {
+            continue;
+        }
+        let [r, g, b] = palette
+            .get(pixel.color as usize)
+            .copied()
+            .ok_or_else(|| anyhow!("Color index {} out of palette range", pixel.color))?;
+        for dy in 0..scale {
+            for dx in 0..scale {
+                img.put_pixel(
+                    pixel.x * scale + dx,
+                    pixel.y * scale + dy,
+                    image::Rgba([r, g, b, 255]),
+                );
+            }
+        }
+    }
+    Ok(img)
+}
+
+/// Parse a palette's hex strings (`#RRGGBB`) into RGB triples.
+fn parse_palette(palette: &Palette) -> Result<Vec<[u8; 3]>> {
+    palette.colors().iter().map(|hex| parse_hex(hex)).collect()
+}
+
+fn parse_hex(hex: &str) -> Result<[u8; 3]> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if hex.len() != 6 {
+        return Err(anyhow!("Invalid hex color: {hex}"));
+    }
+    let r = u8::from_str_radix(&hex[0..2], 16)?;
+    let g = u8::from_str_radix(&hex[2..4], 16)?;
+    let b = u8::from_str_radix(&hex[4..6], 16)?;
+    Ok([r, g, b])
+}
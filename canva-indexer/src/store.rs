@@ -0,0 +1,1098 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod};
+use std::sync::Arc;
+use tokio_postgres::NoTls;
+
+use crate::db::{self, Db, PixelEvent, PixelInfo, PixelState, PlacementOutcome, RetryEntry};
+
+/// Abstract query surface the HTTP handlers depend on.
+///
+/// Mirrors the read-side of `db::*` so the canvas can run on either the
+/// embedded SQLite connection or a real server database without the handlers
+/// caring which. Methods are `async` so a networked backend can await its pool
+/// while the SQLite implementation simply dispatches onto `spawn_blocking`.
+#[async_trait]
+pub trait CanvasStore: Send + Sync {
+    /// Current canvas edge length (width of the latest resize).
+    async fn canvas_size(&self) -> Result<u32>;
+
+    /// Current canvas `(width, height)`, which differ on a non-square board.
+    async fn canvas_dims(&self) -> Result<(u32, u32)>;
+
+    /// Every filled cell of the current canvas.
+    async fn canvas_state(&self) -> Result<Vec<PixelState>>;
+
+    /// `(filled, overwritten)` cell counts.
+    async fn fill_stats(&self) -> Result<(u32, u32)>;
+
+    /// Current value plus recent history for a single cell, if set.
+    async fn pixel_info(&self, x: u32, y: u32) -> Result<Option<PixelInfo>>;
+
+    /// Whether a user has already been ingested.
+    async fn user_exists(&self, public_key: &str) -> Result<bool>;
+
+    /// Record a freshly-resolved user and its homeserver.
+    async fn add_user(&self, public_key: &str, homeserver_pk: &str) -> Result<()>;
+
+    /// Read-only token-bucket balance at `now`: `(whole_tokens, next_token_us)`.
+    async fn credit_status(
+        &self,
+        user_pk: &str,
+        now: i64,
+        regen_us: i64,
+        max_tokens: f64,
+    ) -> Result<(u32, i64)>;
+
+    /// Timestamp of a user's most recent placement, if any.
+    async fn user_last_placement(&self, user_pk: &str) -> Result<Option<i64>>;
+
+    /// Most recent placements (newest first) for the activity feed.
+    async fn recent_events(&self, limit: u32) -> Result<Vec<PixelEvent>>;
+
+    /// Cells changed since `seq`, each paired with its own changefeed sequence
+    /// (ascending), plus the current max sequence.
+    async fn changes_since(&self, seq: i64) -> Result<(Vec<(PixelState, i64)>, i64)>;
+
+    /// Resizes activated with a changefeed `seq` greater than `seq` (ascending):
+    /// `(old_width, old_height, new_width, new_height, seq)`. The re-sync
+    /// counterpart to `changes_since` for the resize sentinel.
+    async fn resizes_since(&self, seq: i64) -> Result<Vec<(u32, u32, u32, u32, i64)>>;
+
+    /// Board dimensions plus the packed color buffer for O(1) full reads (see
+    /// `db::pack_pixel_states` for the layout).
+    async fn canvas_packed(&self) -> Result<(u32, u32, Vec<u8>)>;
+
+    /// Filled cells inside the inclusive `(x0, y0)`–`(x1, y1)` viewport.
+    async fn region(&self, x0: u32, y0: u32, x1: u32, y1: u32) -> Result<Vec<PixelState>>;
+
+    /// Tile-local packed buffer clipped to the `(x0, y0)`–`(x1, y1)` viewport.
+    async fn region_packed(&self, x0: u32, y0: u32, x1: u32, y1: u32)
+        -> Result<(u32, u32, Vec<u8>)>;
+
+    /// The canvas as it looked at `timestamp` (microseconds), replaying history.
+    async fn reconstruct_at(&self, timestamp: i64) -> Result<Vec<PixelState>>;
+
+    // ---- Watcher write path -------------------------------------------------
+    //
+    // The watcher ingests pixels from homeservers and needs to write through
+    // whichever backend is serving reads, so a Postgres deployment actually
+    // accumulates state instead of writing to the local SQLite file no one reads.
+
+    /// All tracked users grouped by homeserver: `(homeserver_pk, [(user_pk, cursor)])`.
+    async fn users_by_homeserver(&self) -> Result<Vec<(String, Vec<(String, String)>)>>;
+
+    /// Advance a user's events-stream cursor after a batch is applied.
+    async fn update_user_cursor(&self, user_pk: &str, cursor: &str) -> Result<()>;
+
+    /// Whether a pixel event id has already been ingested (idempotency check).
+    async fn pixel_event_exists(&self, id: &str) -> Result<bool>;
+
+    /// Resize history ordered by `activated_at` ascending, for placement validation.
+    async fn resize_history(&self) -> Result<Vec<(u32, u32, i64)>>;
+
+    /// Spend one credit and insert a validated pixel event atomically, so a
+    /// transient failure after the spend can't leave the user charged for a
+    /// pixel that was never placed (the spend rolls back along with it).
+    #[allow(clippy::too_many_arguments)]
+    async fn spend_credit_and_insert_pixel(
+        &self,
+        id: &str,
+        user_pk: &str,
+        x: u32,
+        y: u32,
+        color: u8,
+        placed_at: i64,
+        regen_us: i64,
+        max_tokens: f64,
+    ) -> Result<PlacementOutcome>;
+
+    /// Record a resize from `(old_width, old_height)` to `(new_width, new_height)`,
+    /// activating it at `activated_at`. Assigns and returns a fresh changefeed
+    /// `seq` one past the current pixel head, so the resize sorts strictly
+    /// after every pixel placed before it.
+    async fn resize_canvas(
+        &self,
+        old_width: u32,
+        old_height: u32,
+        new_width: u32,
+        new_height: u32,
+        activated_at: i64,
+    ) -> Result<i64>;
+
+    /// Enqueue (or reschedule) a pixel event that failed with a recoverable error.
+    async fn enqueue_retry(
+        &self,
+        pixel_id: &str,
+        user_pk: &str,
+        uri: &str,
+        attempt_count: u32,
+        next_retry_at: i64,
+    ) -> Result<()>;
+
+    /// Retry-queue entries due for another attempt at or before `now`.
+    async fn due_retries(&self, now: i64) -> Result<Vec<RetryEntry>>;
+
+    /// Drop a pixel from the retry queue once it succeeds or is dead-lettered.
+    async fn remove_retry(&self, pixel_id: &str) -> Result<()>;
+
+    /// Move a pixel to the dead-letter table, removing it from the retry queue.
+    async fn dead_letter(
+        &self,
+        pixel_id: &str,
+        user_pk: &str,
+        uri: &str,
+        reason: &str,
+        failed_at: i64,
+    ) -> Result<()>;
+}
+
+/// Embedded SQLite backend wrapping the existing `rusqlite` connection.
+///
+/// Each call hops onto `spawn_blocking` so the synchronous `db::*` helpers never
+/// block the async runtime.
+pub struct SqliteStore {
+    db: Db,
+}
+
+impl SqliteStore {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl CanvasStore for SqliteStore {
+    async fn canvas_size(&self) -> Result<u32> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || db::get_canvas_size(&db)).await?
+    }
+
+    async fn canvas_dims(&self) -> Result<(u32, u32)> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || db::get_canvas_dimensions(&db)).await?
+    }
+
+    async fn canvas_state(&self) -> Result<Vec<PixelState>> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || db::get_canvas_state(&db)).await?
+    }
+
+    async fn fill_stats(&self) -> Result<(u32, u32)> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || db::get_fill_stats(&db)).await?
+    }
+
+    async fn pixel_info(&self, x: u32, y: u32) -> Result<Option<PixelInfo>> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || db::get_pixel_info(&db, x, y)).await?
+    }
+
+    async fn user_exists(&self, public_key: &str) -> Result<bool> {
+        let db = self.db.clone();
+        let pk = public_key.to_string();
+        tokio::task::spawn_blocking(move || db::user_exists(&db, &pk)).await?
+    }
+
+    async fn add_user(&self, public_key: &str, homeserver_pk: &str) -> Result<()> {
+        let db = self.db.clone();
+        let pk = public_key.to_string();
+        let hs = homeserver_pk.to_string();
+        tokio::task::spawn_blocking(move || db::add_user(&db, &pk, &hs)).await?
+    }
+
+    async fn credit_status(
+        &self,
+        user_pk: &str,
+        now: i64,
+        regen_us: i64,
+        max_tokens: f64,
+    ) -> Result<(u32, i64)> {
+        let db = self.db.clone();
+        let upk = user_pk.to_string();
+        tokio::task::spawn_blocking(move || {
+            db::credit_status(&db, &upk, now, regen_us, max_tokens)
+        })
+        .await?
+    }
+
+    async fn user_last_placement(&self, user_pk: &str) -> Result<Option<i64>> {
+        let db = self.db.clone();
+        let upk = user_pk.to_string();
+        tokio::task::spawn_blocking(move || db::get_user_last_placement(&db, &upk)).await?
+    }
+
+    async fn recent_events(&self, limit: u32) -> Result<Vec<PixelEvent>> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || db::get_recent_events(&db, limit)).await?
+    }
+
+    async fn changes_since(&self, seq: i64) -> Result<(Vec<(PixelState, i64)>, i64)> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || db::get_changes_since(&db, seq)).await?
+    }
+
+    async fn resizes_since(&self, seq: i64) -> Result<Vec<(u32, u32, u32, u32, i64)>> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || db::get_resizes_since(&db, seq)).await?
+    }
+
+    async fn canvas_packed(&self) -> Result<(u32, u32, Vec<u8>)> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || db::get_canvas_packed(&db)).await?
+    }
+
+    async fn region(&self, x0: u32, y0: u32, x1: u32, y1: u32) -> Result<Vec<PixelState>> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || db::get_region(&db, x0, y0, x1, y1)).await?
+    }
+
+    async fn region_packed(
+        &self,
+        x0: u32,
+        y0: u32,
+        x1: u32,
+        y1: u32,
+    ) -> Result<(u32, u32, Vec<u8>)> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || db::get_region_packed(&db, x0, y0, x1, y1)).await?
+    }
+
+    async fn reconstruct_at(&self, timestamp: i64) -> Result<Vec<PixelState>> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || db::reconstruct_at(&db, timestamp)).await?
+    }
+
+    async fn users_by_homeserver(&self) -> Result<Vec<(String, Vec<(String, String)>)>> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || db::get_users_by_homeserver(&db)).await?
+    }
+
+    async fn update_user_cursor(&self, user_pk: &str, cursor: &str) -> Result<()> {
+        let db = self.db.clone();
+        let upk = user_pk.to_string();
+        let cur = cursor.to_string();
+        tokio::task::spawn_blocking(move || db::update_user_cursor(&db, &upk, &cur)).await?
+    }
+
+    async fn pixel_event_exists(&self, id: &str) -> Result<bool> {
+        let db = self.db.clone();
+        let id = id.to_string();
+        tokio::task::spawn_blocking(move || db::pixel_event_exists(&db, &id)).await?
+    }
+
+    async fn resize_history(&self) -> Result<Vec<(u32, u32, i64)>> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || db::get_resize_history(&db)).await?
+    }
+
+    async fn spend_credit_and_insert_pixel(
+        &self,
+        id: &str,
+        user_pk: &str,
+        x: u32,
+        y: u32,
+        color: u8,
+        placed_at: i64,
+        regen_us: i64,
+        max_tokens: f64,
+    ) -> Result<PlacementOutcome> {
+        let db = self.db.clone();
+        let id = id.to_string();
+        let upk = user_pk.to_string();
+        tokio::task::spawn_blocking(move || {
+            db::spend_credit_and_insert_pixel(
+                &db, &id, &upk, x, y, color, placed_at, regen_us, max_tokens,
+            )
+        })
+        .await?
+    }
+
+    async fn resize_canvas(
+        &self,
+        old_width: u32,
+        old_height: u32,
+        new_width: u32,
+        new_height: u32,
+        activated_at: i64,
+    ) -> Result<i64> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || {
+            db::resize_canvas(&db, old_width, old_height, new_width, new_height, activated_at)
+        })
+        .await?
+    }
+
+    async fn enqueue_retry(
+        &self,
+        pixel_id: &str,
+        user_pk: &str,
+        uri: &str,
+        attempt_count: u32,
+        next_retry_at: i64,
+    ) -> Result<()> {
+        let db = self.db.clone();
+        let pid = pixel_id.to_string();
+        let upk = user_pk.to_string();
+        let uri = uri.to_string();
+        tokio::task::spawn_blocking(move || {
+            db::enqueue_retry(&db, &pid, &upk, &uri, attempt_count, next_retry_at)
+        })
+        .await?
+    }
+
+    async fn due_retries(&self, now: i64) -> Result<Vec<RetryEntry>> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || db::due_retries(&db, now)).await?
+    }
+
+    async fn remove_retry(&self, pixel_id: &str) -> Result<()> {
+        let db = self.db.clone();
+        let pid = pixel_id.to_string();
+        tokio::task::spawn_blocking(move || db::remove_retry(&db, &pid)).await?
+    }
+
+    async fn dead_letter(
+        &self,
+        pixel_id: &str,
+        user_pk: &str,
+        uri: &str,
+        reason: &str,
+        failed_at: i64,
+    ) -> Result<()> {
+        let db = self.db.clone();
+        let pid = pixel_id.to_string();
+        let upk = user_pk.to_string();
+        let uri = uri.to_string();
+        let reason = reason.to_string();
+        tokio::task::spawn_blocking(move || db::dead_letter(&db, &pid, &upk, &uri, &reason, failed_at))
+            .await?
+    }
+}
+
+/// `tokio-postgres` backend fronted by a `deadpool` connection pool.
+pub struct PostgresStore {
+    pool: Pool,
+    // Postgres keeps no incremental packed snapshot (see `canvas_packed`), so
+    // the bit-depth used to pack on the fly is cached from the config the
+    // store was built with rather than round-tripped through a table.
+    palette_bit_depth: u32,
+}
+
+impl PostgresStore {
+    /// Connect to the configured Postgres instance, ensure the schema exists, and
+    /// seed the initial canvas size if this is a fresh database (mirroring the
+    /// fallback `canvas_resizes` row `db::open` seeds for SQLite).
+    pub async fn connect(url: &str, initial_size: u32, palette_bit_depth: u32) -> Result<Self> {
+        let pg_config: tokio_postgres::Config = url
+            .parse()
+            .context("Failed to parse Postgres connection string")?;
+        let mgr = Manager::from_config(
+            pg_config,
+            NoTls,
+            ManagerConfig {
+                recycling_method: RecyclingMethod::Fast,
+            },
+        );
+        let pool = Pool::builder(mgr)
+            .build()
+            .context("Failed to build Postgres pool")?;
+
+        let store = Self { pool, palette_bit_depth };
+        store.init_schema().await?;
+        store.seed_initial_size(initial_size).await?;
+        Ok(store)
+    }
+
+    /// Insert the first `canvas_resizes` row if none exists yet.
+    async fn seed_initial_size(&self, size: u32) -> Result<()> {
+        let client = self.pool.get().await?;
+        let count: i64 = client
+            .query_one("SELECT COUNT(*) FROM canvas_resizes", &[])
+            .await?
+            .get(0);
+        if count == 0 {
+            client
+                .execute(
+                    "INSERT INTO canvas_resizes (width, height, activated_at) VALUES ($1, $2, 0)",
+                    &[&(size as i32), &(size as i32)],
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn init_schema(&self) -> Result<()> {
+        let client = self.pool.get().await?;
+        client
+            .batch_execute(
+                "
+                CREATE TABLE IF NOT EXISTS users (
+                    public_key TEXT PRIMARY KEY,
+                    homeserver_pk TEXT NOT NULL,
+                    cursor TEXT NOT NULL DEFAULT '',
+                    created_at BIGINT NOT NULL
+                );
+
+                CREATE TABLE IF NOT EXISTS pixel_events (
+                    id TEXT PRIMARY KEY,
+                    user_pk TEXT NOT NULL,
+                    x INTEGER NOT NULL,
+                    y INTEGER NOT NULL,
+                    color INTEGER NOT NULL,
+                    placed_at BIGINT NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS idx_pixel_events_user_placed
+                    ON pixel_events(user_pk, placed_at);
+
+                CREATE TABLE IF NOT EXISTS canvas_state (
+                    x INTEGER NOT NULL,
+                    y INTEGER NOT NULL,
+                    color INTEGER NOT NULL,
+                    user_pk TEXT NOT NULL,
+                    first_user_pk TEXT NOT NULL,
+                    placed_at BIGINT NOT NULL,
+                    was_overwritten INTEGER NOT NULL DEFAULT 0,
+                    seq BIGINT NOT NULL DEFAULT 0,
+                    PRIMARY KEY (x, y)
+                );
+                CREATE INDEX IF NOT EXISTS idx_canvas_state_seq ON canvas_state(seq);
+
+                CREATE TABLE IF NOT EXISTS canvas_resizes (
+                    width INTEGER NOT NULL,
+                    height INTEGER NOT NULL,
+                    activated_at BIGINT NOT NULL,
+                    old_width INTEGER NOT NULL DEFAULT 0,
+                    old_height INTEGER NOT NULL DEFAULT 0,
+                    seq BIGINT NOT NULL DEFAULT 0
+                );
+
+                CREATE TABLE IF NOT EXISTS user_credits (
+                    user_pk TEXT PRIMARY KEY,
+                    tokens DOUBLE PRECISION NOT NULL,
+                    updated_at BIGINT NOT NULL
+                );
+
+                CREATE TABLE IF NOT EXISTS pixel_retry_queue (
+                    pixel_id TEXT PRIMARY KEY,
+                    user_pk TEXT NOT NULL,
+                    uri TEXT NOT NULL,
+                    attempt_count INTEGER NOT NULL,
+                    next_retry_at BIGINT NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS idx_pixel_retry_next
+                    ON pixel_retry_queue(next_retry_at);
+
+                CREATE TABLE IF NOT EXISTS pixel_dead_letter (
+                    pixel_id TEXT PRIMARY KEY,
+                    user_pk TEXT NOT NULL,
+                    uri TEXT NOT NULL,
+                    reason TEXT NOT NULL,
+                    failed_at BIGINT NOT NULL
+                );
+                ",
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CanvasStore for PostgresStore {
+    async fn canvas_size(&self) -> Result<u32> {
+        let client = self.pool.get().await?;
+        let row = client
+            .query_one(
+                "SELECT width FROM canvas_resizes ORDER BY activated_at DESC LIMIT 1",
+                &[],
+            )
+            .await?;
+        let width: i32 = row.get(0);
+        Ok(width as u32)
+    }
+
+    async fn canvas_dims(&self) -> Result<(u32, u32)> {
+        let client = self.pool.get().await?;
+        let row = client
+            .query_one(
+                "SELECT width, height FROM canvas_resizes ORDER BY activated_at DESC LIMIT 1",
+                &[],
+            )
+            .await?;
+        Ok((row.get::<_, i32>(0) as u32, row.get::<_, i32>(1) as u32))
+    }
+
+    async fn canvas_state(&self) -> Result<Vec<PixelState>> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query("SELECT x, y, color, user_pk, placed_at FROM canvas_state", &[])
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| PixelState {
+                x: row.get::<_, i32>(0) as u32,
+                y: row.get::<_, i32>(1) as u32,
+                color: row.get::<_, i32>(2) as u8,
+                user_pk: row.get(3),
+                placed_at: row.get(4),
+            })
+            .collect())
+    }
+
+    async fn fill_stats(&self) -> Result<(u32, u32)> {
+        let client = self.pool.get().await?;
+        let filled: i64 = client
+            .query_one("SELECT COUNT(*) FROM canvas_state", &[])
+            .await?
+            .get(0);
+        let overwritten: i64 = client
+            .query_one(
+                "SELECT COUNT(*) FROM canvas_state WHERE was_overwritten = 1",
+                &[],
+            )
+            .await?
+            .get(0);
+        Ok((filled as u32, overwritten as u32))
+    }
+
+    async fn pixel_info(&self, x: u32, y: u32) -> Result<Option<PixelInfo>> {
+        let client = self.pool.get().await?;
+        let current = client
+            .query_opt(
+                "SELECT x, y, color, user_pk, placed_at FROM canvas_state WHERE x = $1 AND y = $2",
+                &[&(x as i32), &(y as i32)],
+            )
+            .await?
+            .map(|row| PixelState {
+                x: row.get::<_, i32>(0) as u32,
+                y: row.get::<_, i32>(1) as u32,
+                color: row.get::<_, i32>(2) as u8,
+                user_pk: row.get(3),
+                placed_at: row.get(4),
+            });
+
+        let current = match current {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+
+        let rows = client
+            .query(
+                "SELECT id, user_pk, color, placed_at FROM pixel_events \
+                 WHERE x = $1 AND y = $2 ORDER BY placed_at DESC LIMIT 10",
+                &[&(x as i32), &(y as i32)],
+            )
+            .await?;
+        let history = rows
+            .iter()
+            .map(|row| db::PixelHistoryEntry {
+                id: row.get(0),
+                user_pk: row.get(1),
+                color: row.get::<_, i32>(2) as u8,
+                placed_at: row.get(3),
+            })
+            .collect();
+
+        Ok(Some(PixelInfo { current, history }))
+    }
+
+    async fn user_exists(&self, public_key: &str) -> Result<bool> {
+        let client = self.pool.get().await?;
+        let count: i64 = client
+            .query_one(
+                "SELECT COUNT(*) FROM users WHERE public_key = $1",
+                &[&public_key],
+            )
+            .await?
+            .get(0);
+        Ok(count > 0)
+    }
+
+    async fn add_user(&self, public_key: &str, homeserver_pk: &str) -> Result<()> {
+        let client = self.pool.get().await?;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        client
+            .execute(
+                "INSERT INTO users (public_key, homeserver_pk, created_at) VALUES ($1, $2, $3) \
+                 ON CONFLICT (public_key) DO NOTHING",
+                &[&public_key, &homeserver_pk, &now],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn credit_status(
+        &self,
+        user_pk: &str,
+        now: i64,
+        regen_us: i64,
+        max_tokens: f64,
+    ) -> Result<(u32, i64)> {
+        let client = self.pool.get().await?;
+        let row = client
+            .query_opt(
+                "SELECT tokens, updated_at FROM user_credits WHERE user_pk = $1",
+                &[&user_pk],
+            )
+            .await?;
+        let (tokens, updated_at) = row
+            .map(|r| (r.get::<_, f64>(0), r.get::<_, i64>(1)))
+            .unwrap_or((max_tokens, now));
+        let available =
+            (tokens + (now - updated_at).max(0) as f64 / regen_us as f64).min(max_tokens);
+        let next_token_us = if available >= 1.0 {
+            0
+        } else {
+            ((1.0 - available) * regen_us as f64).ceil() as i64
+        };
+        Ok((available.floor() as u32, next_token_us))
+    }
+
+    async fn user_last_placement(&self, user_pk: &str) -> Result<Option<i64>> {
+        let client = self.pool.get().await?;
+        Ok(client
+            .query_opt(
+                "SELECT placed_at FROM pixel_events WHERE user_pk = $1 \
+                 ORDER BY placed_at DESC LIMIT 1",
+                &[&user_pk],
+            )
+            .await?
+            .map(|row| row.get(0)))
+    }
+
+    async fn recent_events(&self, limit: u32) -> Result<Vec<PixelEvent>> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                "SELECT id, user_pk, x, y, color, placed_at FROM pixel_events \
+                 ORDER BY placed_at DESC LIMIT $1",
+                &[&(limit as i64)],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| PixelEvent {
+                id: row.get(0),
+                user_pk: row.get(1),
+                x: row.get::<_, i32>(2) as u32,
+                y: row.get::<_, i32>(3) as u32,
+                color: row.get::<_, i32>(4) as u8,
+                placed_at: row.get(5),
+            })
+            .collect())
+    }
+
+    async fn changes_since(&self, seq: i64) -> Result<(Vec<(PixelState, i64)>, i64)> {
+        let client = self.pool.get().await?;
+        let max_seq: i64 = client
+            .query_one("SELECT COALESCE(MAX(seq), 0) FROM canvas_state", &[])
+            .await?
+            .get(0);
+        let rows = client
+            .query(
+                "SELECT x, y, color, user_pk, placed_at, seq FROM canvas_state \
+                 WHERE seq > $1 ORDER BY seq ASC",
+                &[&seq],
+            )
+            .await?;
+        let changes = rows
+            .iter()
+            .map(|row| {
+                (
+                    PixelState {
+                        x: row.get::<_, i32>(0) as u32,
+                        y: row.get::<_, i32>(1) as u32,
+                        color: row.get::<_, i32>(2) as u8,
+                        user_pk: row.get(3),
+                        placed_at: row.get(4),
+                    },
+                    row.get(5),
+                )
+            })
+            .collect();
+        Ok((changes, max_seq))
+    }
+
+    async fn resizes_since(&self, seq: i64) -> Result<Vec<(u32, u32, u32, u32, i64)>> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                "SELECT old_width, old_height, width, height, seq FROM canvas_resizes \
+                 WHERE seq > $1 ORDER BY seq ASC",
+                &[&seq],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| {
+                (
+                    row.get::<_, i32>(0) as u32,
+                    row.get::<_, i32>(1) as u32,
+                    row.get::<_, i32>(2) as u32,
+                    row.get::<_, i32>(3) as u32,
+                    row.get(4),
+                )
+            })
+            .collect())
+    }
+
+    async fn canvas_packed(&self) -> Result<(u32, u32, Vec<u8>)> {
+        // Postgres doesn't keep the incremental snapshot blob; pack on the fly.
+        let (width, height) = self.canvas_dims().await?;
+        let pixels = self.canvas_state().await?;
+        let packed = db::pack_pixel_states(&pixels, width, height, self.palette_bit_depth);
+        Ok((width, height, packed))
+    }
+
+    async fn region(&self, x0: u32, y0: u32, x1: u32, y1: u32) -> Result<Vec<PixelState>> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                "SELECT x, y, color, user_pk, placed_at FROM canvas_state \
+                 WHERE x >= $1 AND x <= $3 AND y >= $2 AND y <= $4",
+                &[&(x0 as i32), &(y0 as i32), &(x1 as i32), &(y1 as i32)],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| PixelState {
+                x: row.get::<_, i32>(0) as u32,
+                y: row.get::<_, i32>(1) as u32,
+                color: row.get::<_, i32>(2) as u8,
+                user_pk: row.get(3),
+                placed_at: row.get(4),
+            })
+            .collect())
+    }
+
+    async fn region_packed(
+        &self,
+        x0: u32,
+        y0: u32,
+        x1: u32,
+        y1: u32,
+    ) -> Result<(u32, u32, Vec<u8>)> {
+        let width = x1.saturating_sub(x0) + 1;
+        let height = y1.saturating_sub(y0) + 1;
+        let pixels = self.region(x0, y0, x1, y1).await?;
+        let packed = db::pack_region(&pixels, x0, y0, width, height, self.palette_bit_depth);
+        Ok((width, height, packed))
+    }
+
+    async fn reconstruct_at(&self, timestamp: i64) -> Result<Vec<PixelState>> {
+        let client = self.pool.get().await?;
+        // `None` means `timestamp` predates the first resize, i.e. the canvas
+        // did not exist yet, so treat it as an empty (0x0) board.
+        let (width, height): (i32, i32) = client
+            .query_opt(
+                "SELECT width, height FROM canvas_resizes WHERE activated_at <= $1 \
+                 ORDER BY activated_at DESC LIMIT 1",
+                &[&timestamp],
+            )
+            .await?
+            .map(|row| (row.get(0), row.get(1)))
+            .unwrap_or((0, 0));
+        let rows = client
+            .query(
+                "SELECT DISTINCT ON (x, y) x, y, color, user_pk, placed_at \
+                 FROM pixel_events WHERE placed_at <= $1 \
+                 ORDER BY x, y, placed_at DESC",
+                &[&timestamp],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| PixelState {
+                x: row.get::<_, i32>(0) as u32,
+                y: row.get::<_, i32>(1) as u32,
+                color: row.get::<_, i32>(2) as u8,
+                user_pk: row.get(3),
+                placed_at: row.get(4),
+            })
+            .filter(|p| (p.x as i32) < width && (p.y as i32) < height)
+            .collect())
+    }
+
+    async fn users_by_homeserver(&self) -> Result<Vec<(String, Vec<(String, String)>)>> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                "SELECT public_key, homeserver_pk, cursor FROM users ORDER BY homeserver_pk",
+                &[],
+            )
+            .await?;
+        let mut groups: std::collections::HashMap<String, Vec<(String, String)>> =
+            std::collections::HashMap::new();
+        for row in rows {
+            let user_pk: String = row.get(0);
+            let hs_pk: String = row.get(1);
+            let cursor: String = row.get(2);
+            groups.entry(hs_pk).or_default().push((user_pk, cursor));
+        }
+        Ok(groups.into_iter().collect())
+    }
+
+    async fn update_user_cursor(&self, user_pk: &str, cursor: &str) -> Result<()> {
+        let client = self.pool.get().await?;
+        client
+            .execute(
+                "UPDATE users SET cursor = $1 WHERE public_key = $2",
+                &[&cursor, &user_pk],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn pixel_event_exists(&self, id: &str) -> Result<bool> {
+        let client = self.pool.get().await?;
+        let count: i64 = client
+            .query_one("SELECT COUNT(*) FROM pixel_events WHERE id = $1", &[&id])
+            .await?
+            .get(0);
+        Ok(count > 0)
+    }
+
+    async fn resize_history(&self) -> Result<Vec<(u32, u32, i64)>> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                "SELECT width, height, activated_at FROM canvas_resizes ORDER BY activated_at ASC",
+                &[],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| {
+                (
+                    row.get::<_, i32>(0) as u32,
+                    row.get::<_, i32>(1) as u32,
+                    row.get(2),
+                )
+            })
+            .collect())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn spend_credit_and_insert_pixel(
+        &self,
+        id: &str,
+        user_pk: &str,
+        x: u32,
+        y: u32,
+        color: u8,
+        placed_at: i64,
+        regen_us: i64,
+        max_tokens: f64,
+    ) -> Result<PlacementOutcome> {
+        let mut client = self.pool.get().await?;
+        let tx = client.transaction().await?;
+
+        let row = tx
+            .query_opt(
+                "SELECT tokens, updated_at FROM user_credits WHERE user_pk = $1",
+                &[&user_pk],
+            )
+            .await?;
+        let (tokens, updated_at) = row
+            .map(|r| (r.get::<_, f64>(0), r.get::<_, i64>(1)))
+            .unwrap_or((max_tokens, placed_at));
+        let available = db::refill(tokens, updated_at, placed_at, regen_us, max_tokens);
+        let allowed = available >= 1.0;
+        let remaining = if allowed { available - 1.0 } else { available };
+
+        tx.execute(
+            "INSERT INTO user_credits (user_pk, tokens, updated_at) VALUES ($1, $2, $3) \
+             ON CONFLICT (user_pk) DO UPDATE SET tokens = $2, updated_at = $3",
+            &[&user_pk, &remaining, &placed_at],
+        )
+        .await?;
+
+        if !allowed {
+            tx.commit().await?;
+            return Ok(PlacementOutcome::InsufficientCredits {
+                next_token_us: db::next_token_us(remaining, regen_us),
+            });
+        }
+
+        tx.execute(
+            "INSERT INTO pixel_events (id, user_pk, x, y, color, placed_at) VALUES ($1, $2, $3, $4, $5, $6)",
+            &[&id, &user_pk, &(x as i32), &(y as i32), &(color as i32), &placed_at],
+        )
+        .await?;
+
+        // One past both the pixel and the resize heads, so a pixel placed after
+        // a resize never shares that resize's seq.
+        let next_seq: i64 = tx
+            .query_one(
+                "SELECT GREATEST(COALESCE((SELECT MAX(seq) FROM canvas_state), 0), \
+                 COALESCE((SELECT MAX(seq) FROM canvas_resizes), 0)) + 1",
+                &[],
+            )
+            .await?
+            .get(0);
+
+        let existing: Option<(String, i32)> = tx
+            .query_opt(
+                "SELECT first_user_pk, was_overwritten FROM canvas_state WHERE x = $1 AND y = $2",
+                &[&(x as i32), &(y as i32)],
+            )
+            .await?
+            .map(|row| (row.get(0), row.get(1)));
+
+        let (was_new, was_overwritten) = match existing {
+            None => {
+                tx.execute(
+                    "INSERT INTO canvas_state (x, y, color, user_pk, first_user_pk, placed_at, was_overwritten, seq) \
+                     VALUES ($1, $2, $3, $4, $4, $5, 0, $6)",
+                    &[&(x as i32), &(y as i32), &(color as i32), &user_pk, &placed_at, &next_seq],
+                )
+                .await?;
+                (true, false)
+            }
+            Some((first_user, was_overwritten)) => {
+                let newly_overwritten = was_overwritten == 0 && first_user != user_pk;
+                let ow_val: i32 = if newly_overwritten || was_overwritten != 0 { 1 } else { 0 };
+                tx.execute(
+                    "UPDATE canvas_state SET color = $1, user_pk = $2, placed_at = $3, was_overwritten = $4, seq = $5 \
+                     WHERE x = $6 AND y = $7",
+                    &[&(color as i32), &user_pk, &placed_at, &ow_val, &next_seq, &(x as i32), &(y as i32)],
+                )
+                .await?;
+                (false, newly_overwritten)
+            }
+        };
+
+        tx.commit().await?;
+        Ok(PlacementOutcome::Inserted {
+            was_new,
+            was_overwritten,
+            seq: next_seq,
+        })
+    }
+
+    async fn resize_canvas(
+        &self,
+        old_width: u32,
+        old_height: u32,
+        new_width: u32,
+        new_height: u32,
+        activated_at: i64,
+    ) -> Result<i64> {
+        let mut client = self.pool.get().await?;
+        let tx = client.transaction().await?;
+        let seq: i64 = tx
+            .query_one("SELECT COALESCE(MAX(seq), 0) + 1 FROM canvas_state", &[])
+            .await?
+            .get(0);
+        tx.execute(
+            "INSERT INTO canvas_resizes (width, height, activated_at, old_width, old_height, seq) \
+             VALUES ($1, $2, $3, $4, $5, $6)",
+            &[
+                &(new_width as i32),
+                &(new_height as i32),
+                &activated_at,
+                &(old_width as i32),
+                &(old_height as i32),
+                &seq,
+            ],
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(seq)
+    }
+
+    async fn enqueue_retry(
+        &self,
+        pixel_id: &str,
+        user_pk: &str,
+        uri: &str,
+        attempt_count: u32,
+        next_retry_at: i64,
+    ) -> Result<()> {
+        let client = self.pool.get().await?;
+        client
+            .execute(
+                "INSERT INTO pixel_retry_queue (pixel_id, user_pk, uri, attempt_count, next_retry_at) \
+                 VALUES ($1, $2, $3, $4, $5) \
+                 ON CONFLICT (pixel_id) DO UPDATE SET attempt_count = $4, next_retry_at = $5",
+                &[&pixel_id, &user_pk, &uri, &(attempt_count as i32), &next_retry_at],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn due_retries(&self, now: i64) -> Result<Vec<RetryEntry>> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                "SELECT pixel_id, user_pk, uri, attempt_count FROM pixel_retry_queue \
+                 WHERE next_retry_at <= $1 ORDER BY next_retry_at",
+                &[&now],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| RetryEntry {
+                pixel_id: row.get(0),
+                user_pk: row.get(1),
+                uri: row.get(2),
+                attempt_count: row.get::<_, i32>(3) as u32,
+            })
+            .collect())
+    }
+
+    async fn remove_retry(&self, pixel_id: &str) -> Result<()> {
+        let client = self.pool.get().await?;
+        client
+            .execute("DELETE FROM pixel_retry_queue WHERE pixel_id = $1", &[&pixel_id])
+            .await?;
+        Ok(())
+    }
+
+    async fn dead_letter(
+        &self,
+        pixel_id: &str,
+        user_pk: &str,
+        uri: &str,
+        reason: &str,
+        failed_at: i64,
+    ) -> Result<()> {
+        let client = self.pool.get().await?;
+        client
+            .execute(
+                "INSERT INTO pixel_dead_letter (pixel_id, user_pk, uri, reason, failed_at) \
+                 VALUES ($1, $2, $3, $4, $5) \
+                 ON CONFLICT (pixel_id) DO UPDATE SET reason = $4, failed_at = $5",
+                &[&pixel_id, &user_pk, &uri, &reason, &failed_at],
+            )
+            .await?;
+        client
+            .execute("DELETE FROM pixel_retry_queue WHERE pixel_id = $1", &[&pixel_id])
+            .await?;
+        Ok(())
+    }
+}
+
+/// Build the configured store backend.
+pub async fn from_config(
+    config: &crate::config::DatabaseConfig,
+    canvas: &crate::config::CanvasConfig,
+    db: Db,
+) -> Result<Arc<dyn CanvasStore>> {
+    match config.backend {
+        crate::config::StoreBackend::Sqlite => Ok(Arc::new(SqliteStore::new(db))),
+        crate::config::StoreBackend::Postgres => {
+            let url = config
+                .postgres_url
+                .as_deref()
+                .context("[database] backend = \"postgres\" requires postgres_url")?;
+            let bit_depth = canvas.palette().context("Invalid [canvas] palette")?.bit_depth();
+            Ok(Arc::new(
+                PostgresStore::connect(url, canvas.initial_size, bit_depth).await?,
+            ))
+        }
+    }
+}
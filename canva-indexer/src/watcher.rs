@@ -1,37 +1,63 @@
 use anyhow::{anyhow, Result};
 use pubky::Pubky;
 use std::sync::Arc;
-use tokio::sync::broadcast;
 use tracing::{debug, error, info, warn};
 
 use crate::config::Config;
-use crate::db::{self, Db, PixelState};
+use crate::events::EventBus;
+use crate::db::{PixelState, PlacementOutcome};
+use crate::metrics::Metrics;
 use crate::pixel::{self, CanvaPixel};
+use crate::store::CanvasStore;
 
-#[derive(Clone, Debug, serde::Serialize)]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type")]
 pub enum SseEvent {
     #[serde(rename = "pixel")]
-    Pixel(PixelState),
+    Pixel {
+        #[serde(flatten)]
+        pixel: PixelState,
+        /// Monotonic changefeed sequence assigned at `db::spend_credit_and_insert_pixel` time, so
+        /// a lagged subscriber can re-sync from where it left off.
+        seq: i64,
+    },
     #[serde(rename = "resize")]
     Resize {
         old_width: u32,
         old_height: u32,
         new_width: u32,
         new_height: u32,
+        /// Changefeed sequence assigned to the resize: one past the pixel head
+        /// at the moment of the resize.
+        seq: i64,
     },
 }
 
+impl SseEvent {
+    /// The changefeed sequence this event carries — the cursor a reconnecting
+    /// client echoes back to resume without gaps.
+    pub fn seq(&self) -> i64 {
+        match self {
+            SseEvent::Pixel { seq, .. } | SseEvent::Resize { seq, .. } => *seq,
+        }
+    }
+}
+
 pub async fn run(
-    db: Db,
+    store: Arc<dyn CanvasStore>,
     pubky: Arc<Pubky>,
     config: Config,
-    sse_tx: broadcast::Sender<SseEvent>,
+    events: Arc<dyn EventBus>,
+    metrics: Arc<Metrics>,
     mut shutdown_rx: tokio::sync::watch::Receiver<bool>,
 ) {
     let poll_interval = std::time::Duration::from_millis(config.watcher.poll_interval_ms);
     let mut interval = tokio::time::interval(poll_interval);
 
+    // Drain the durable retry queue on its own cadence, one base-delay apart.
+    let retry_interval = std::time::Duration::from_secs(config.watcher.retry_base_seconds.max(1));
+    let mut retry_tick = tokio::time::interval(retry_interval);
+
     info!("Watcher started, polling every {}ms", config.watcher.poll_interval_ms);
 
     loop {
@@ -41,26 +67,28 @@ pub async fn run(
                 break;
             }
             _ = interval.tick() => {
-                if let Err(e) = poll_cycle(&db, &pubky, &config, &sse_tx).await {
+                if let Err(e) = poll_cycle(&store, &pubky, &config, &events, &metrics).await {
                     error!("Poll cycle error: {e:?}");
                 }
             }
+            _ = retry_tick.tick() => {
+                if let Err(e) = drain_retries(store.as_ref(), &pubky, &config, &events, &metrics).await {
+                    error!("Retry drain error: {e:?}");
+                }
+            }
         }
     }
 }
 
 async fn poll_cycle(
-    db: &Db,
+    store: &Arc<dyn CanvasStore>,
     pubky: &Pubky,
     config: &Config,
-    sse_tx: &broadcast::Sender<SseEvent>,
+    events: &dyn EventBus,
+    metrics: &Arc<Metrics>,
 ) -> Result<()> {
     // Get all users grouped by homeserver
-    let groups = {
-        let db = db.clone();
-        tokio::task::spawn_blocking(move || db::get_users_by_homeserver(&db))
-            .await??
-    };
+    let groups = store.users_by_homeserver().await?;
 
     if groups.is_empty() {
         debug!("No users to poll");
@@ -68,22 +96,25 @@ async fn poll_cycle(
     }
 
     for (hs_pk, users) in &groups {
-        if let Err(e) = poll_homeserver(db, pubky, config, sse_tx, hs_pk, users).await {
+        if let Err(e) =
+            poll_homeserver(store.as_ref(), pubky, config, events, metrics, hs_pk, users).await
+        {
             warn!("Error polling homeserver {hs_pk}: {e}");
         }
     }
 
-    check_resize(db, config, sse_tx).await?;
+    check_resize(store, config, events).await?;
 
     Ok(())
 }
 
 /// Poll a homeserver using /events-stream with per-user cursors and path filtering
 async fn poll_homeserver(
-    db: &Db,
+    store: &dyn CanvasStore,
     pubky: &Pubky,
     config: &Config,
-    sse_tx: &broadcast::Sender<SseEvent>,
+    events: &dyn EventBus,
+    metrics: &Arc<Metrics>,
     hs_pk: &str,
     users: &[(String, String)],
 ) -> Result<()> {
@@ -110,47 +141,74 @@ async fn poll_homeserver(
         .await
         .map_err(|e| anyhow!("HTTP error polling {hs_pk}: {e}"))?;
 
-    let text = response.text().await?;
-    if text.trim().is_empty() {
-        return Ok(());
-    }
-
-    // Parse SSE events from the response
-    // Format:
+    // Consume the events-stream body as a byte stream rather than buffering the
+    // whole thing: homeservers may serve long-lived or chunked streams, and a
+    // read can land mid-event, mid-`data:` line, or in the middle of a multi-byte
+    // UTF-8 sequence. The parser keeps a leftover buffer across reads so partial
+    // lines and split characters are re-joined instead of dropped.
+    //
+    // Event block format:
     //   event: PUT
     //   data: pubky://user_pk/pub/pubky-canva/pixels/id
     //   data: cursor: 42
     //   data: content_hash: ...
     //   (blank line)
-    let events = parse_sse_response(&text);
-    debug!("Homeserver {hs_pk}: {} SSE events", events.len());
-
-    for event in &events {
-        if event.event_type != "PUT" {
-            continue;
+    use tokio_stream::StreamExt;
+    let mut stream = response.bytes_stream();
+    let mut parser = SseStreamParser::default();
+    let mut count = 0usize;
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| anyhow!("Stream error polling {hs_pk}: {e}"))?;
+        for event in parser.push_bytes(&chunk) {
+            count += 1;
+            apply_sse_event(store, pubky, config, events, metrics, users, &event).await?;
         }
+    }
+    // A stream may end without a trailing blank line; flush the pending event.
+    if let Some(event) = parser.finish() {
+        count += 1;
+        apply_sse_event(store, pubky, config, events, metrics, users, &event).await?;
+    }
 
-        if let Some((user_pk, pixel_id)) = parse_pixel_uri(&event.uri) {
-            // Check if this user is one we're tracking
-            let is_tracked = users.iter().any(|(pk, _)| pk == user_pk);
-            if !is_tracked {
-                continue;
-            }
+    debug!("Homeserver {hs_pk}: {count} SSE events");
 
-            match process_pixel_event(db, pubky, config, sse_tx, user_pk, pixel_id, &event.uri).await {
-                Ok(()) => {}
-                Err(e) => warn!("Error processing pixel {pixel_id} from {user_pk}: {e}"),
-            }
+    Ok(())
+}
 
-            // Update this user's cursor
-            if !event.cursor.is_empty() {
-                let db = db.clone();
-                let upk = user_pk.to_string();
-                let cur = event.cursor.clone();
-                tokio::task::spawn_blocking(move || db::update_user_cursor(&db, &upk, &cur))
-                    .await??;
-            }
-        }
+/// Apply a single parsed SSE event: ingest the pixel it references (if we track
+/// that user) and advance the user's cursor.
+async fn apply_sse_event(
+    store: &dyn CanvasStore,
+    pubky: &Pubky,
+    config: &Config,
+    events: &dyn EventBus,
+    metrics: &Arc<Metrics>,
+    users: &[(String, String)],
+    event: &SseEventParsed,
+) -> Result<()> {
+    if event.event_type != "PUT" {
+        return Ok(());
+    }
+
+    let Some((user_pk, pixel_id)) = parse_pixel_uri(&event.uri) else {
+        return Ok(());
+    };
+
+    // Check if this user is one we're tracking
+    if !users.iter().any(|(pk, _)| pk == user_pk) {
+        return Ok(());
+    }
+
+    // On failure, durably queue (or dead-letter) the event rather than dropping
+    // it when the cursor advances below.
+    if let Err(err) = process_pixel_event(store, pubky, config, events, metrics, user_pk, pixel_id, &event.uri).await {
+        handle_failure(store, config, user_pk, pixel_id, &event.uri, 0, err).await?;
+    }
+
+    // Update this user's cursor
+    if !event.cursor.is_empty() {
+        store.update_user_cursor(user_pk, &event.cursor).await?;
     }
 
     Ok(())
@@ -163,48 +221,86 @@ struct SseEventParsed {
     cursor: String,
 }
 
-/// Parse SSE-format response into structured events
-fn parse_sse_response(text: &str) -> Vec<SseEventParsed> {
-    let mut events = Vec::new();
-    let mut current_type = String::new();
-    let mut current_uri = String::new();
-    let mut current_cursor = String::new();
+/// Stateful incremental parser for the SSE-format events-stream body.
+///
+/// Bytes arrive in arbitrary chunks, so the parser only decodes and consumes
+/// whole `\n`-terminated lines: a trailing partial line — which may even end in
+/// the middle of a multi-byte UTF-8 sequence — is held back in `buffer` until
+/// the next read completes it. A blank line closes the current `event`/`data`
+/// block and emits an [`SseEventParsed`].
+#[derive(Default)]
+struct SseStreamParser {
+    buffer: Vec<u8>,
+    current_type: String,
+    current_uri: String,
+    current_cursor: String,
+}
+
+impl SseStreamParser {
+    /// Feed a chunk of stream bytes, returning any events it completes.
+    fn push_bytes(&mut self, bytes: &[u8]) -> Vec<SseEventParsed> {
+        self.buffer.extend_from_slice(bytes);
+        let mut events = Vec::new();
+
+        // Consume only whole lines; anything after the last newline (possibly a
+        // split UTF-8 character) stays buffered for the next call.
+        while let Some(nl) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line_bytes: Vec<u8> = self.buffer.drain(..=nl).collect();
+            let line = String::from_utf8_lossy(&line_bytes[..line_bytes.len() - 1]);
+            let line = line.strip_suffix('\r').unwrap_or(&line);
+            self.push_line(line, &mut events);
+        }
+
+        events
+    }
+
+    /// Flush any event left pending when the stream ends without a trailing
+    /// blank line, treating leftover bytes as a final unterminated line.
+    fn finish(&mut self) -> Option<SseEventParsed> {
+        if !self.buffer.is_empty() {
+            let line = String::from_utf8_lossy(&std::mem::take(&mut self.buffer)).to_string();
+            let line = line.strip_suffix('\r').unwrap_or(&line);
+            let mut events = Vec::new();
+            self.push_line(line, &mut events);
+            if let Some(event) = events.pop() {
+                return Some(event);
+            }
+        }
 
-    for line in text.lines() {
+        if !self.current_type.is_empty() && !self.current_uri.is_empty() {
+            return Some(SseEventParsed {
+                event_type: std::mem::take(&mut self.current_type),
+                uri: std::mem::take(&mut self.current_uri),
+                cursor: std::mem::take(&mut self.current_cursor),
+            });
+        }
+
+        None
+    }
+
+    /// Fold a single decoded line into the in-progress event, emitting it when a
+    /// blank line closes the block.
+    fn push_line(&mut self, line: &str, events: &mut Vec<SseEventParsed>) {
         if let Some(event_type) = line.strip_prefix("event: ") {
-            current_type = event_type.trim().to_string();
+            self.current_type = event_type.trim().to_string();
         } else if let Some(data) = line.strip_prefix("data: ") {
             let data = data.trim();
             if let Some(cursor) = data.strip_prefix("cursor: ") {
-                current_cursor = cursor.to_string();
+                self.current_cursor = cursor.to_string();
             } else if data.starts_with("content_hash:") {
                 // Skip content_hash lines
             } else if !data.is_empty() {
-                current_uri = data.to_string();
+                self.current_uri = data.to_string();
             }
-        } else if line.is_empty() && !current_type.is_empty() {
+        } else if line.is_empty() && !self.current_type.is_empty() {
             // End of event block
             events.push(SseEventParsed {
-                event_type: current_type.clone(),
-                uri: current_uri.clone(),
-                cursor: current_cursor.clone(),
+                event_type: std::mem::take(&mut self.current_type),
+                uri: std::mem::take(&mut self.current_uri),
+                cursor: std::mem::take(&mut self.current_cursor),
             });
-            current_type.clear();
-            current_uri.clear();
-            current_cursor.clear();
         }
     }
-
-    // Handle last event if no trailing blank line
-    if !current_type.is_empty() && !current_uri.is_empty() {
-        events.push(SseEventParsed {
-            event_type: current_type,
-            uri: current_uri,
-            cursor: current_cursor,
-        });
-    }
-
-    events
 }
 
 /// Parse a pubky URI to extract user_pk and pixel_id
@@ -219,124 +315,236 @@ fn parse_pixel_uri(uri: &str) -> Option<(&str, &str)> {
     Some((user_pk, pixel_id))
 }
 
+/// Why a pixel event failed to process, classified for the retry/dead-letter
+/// machinery: transient failures (network/HTTP, or a validation race against a
+/// not-yet-applied resize) are re-enqueued with backoff, while permanent ones
+/// (malformed data, invalid timestamp, no credits) are dead-lettered at once.
+enum ProcessError {
+    Transient(anyhow::Error),
+    Permanent(anyhow::Error),
+}
+
+impl ProcessError {
+    /// Human-readable cause stored on the queue/dead-letter row.
+    fn reason(&self) -> String {
+        match self {
+            ProcessError::Transient(e) | ProcessError::Permanent(e) => e.to_string(),
+        }
+    }
+}
+
 async fn process_pixel_event(
-    db: &Db,
+    store: &dyn CanvasStore,
     pubky: &Pubky,
     config: &Config,
-    sse_tx: &broadcast::Sender<SseEvent>,
+    events: &dyn EventBus,
+    metrics: &Arc<Metrics>,
     user_pk: &str,
     pixel_id: &str,
     uri: &str,
-) -> Result<()> {
+) -> std::result::Result<(), ProcessError> {
+    use ProcessError::{Permanent, Transient};
+
     // Check if already processed
-    {
-        let db = db.clone();
-        let id = pixel_id.to_string();
-        if tokio::task::spawn_blocking(move || db::pixel_event_exists(&db, &id)).await?? {
-            debug!("Pixel event {pixel_id} already processed");
-            return Ok(());
-        }
+    if store.pixel_event_exists(pixel_id).await.map_err(Transient)? {
+        debug!("Pixel event {pixel_id} already processed");
+        return Ok(());
     }
 
     // Parse timestamp from ID
     let timestamp = pixel::parse_timestamp_id(pixel_id)
-        .map_err(|e| anyhow!("Invalid pixel ID {pixel_id}: {e}"))?;
+        .map_err(|e| Permanent(anyhow!("Invalid pixel ID {pixel_id}: {e}")))?;
 
     // Validate timestamp
     pixel::validate_timestamp(timestamp)
-        .map_err(|e| anyhow!("Invalid timestamp for {pixel_id}: {e}"))?;
+        .map_err(|e| Permanent(anyhow!("Invalid timestamp for {pixel_id}: {e}")))?;
 
     // Fetch pixel data from homeserver
     let response = pubky.public_storage().get(uri).await
-        .map_err(|e| anyhow!("Failed to fetch pixel data: {e}"))?;
+        .map_err(|e| Transient(anyhow!("Failed to fetch pixel data: {e}")))?;
 
     if !response.status().is_success() {
-        return Err(anyhow!("Failed to fetch pixel: HTTP {}", response.status()));
+        return Err(Transient(anyhow!(
+            "Failed to fetch pixel: HTTP {}",
+            response.status()
+        )));
     }
 
-    let blob = response.bytes().await?;
+    let blob = response
+        .bytes()
+        .await
+        .map_err(|e| Transient(anyhow!("Failed to read pixel body: {e}")))?;
     let pixel: CanvaPixel = serde_json::from_slice(&blob)
-        .map_err(|e| anyhow!("Invalid pixel JSON: {e}"))?;
+        .map_err(|e| Permanent(anyhow!("Invalid pixel JSON: {e}")))?;
 
     // Get canvas dimensions and resize history for validation
-    let (canvas_width, canvas_height, resize_history) = {
-        let db = db.clone();
-        tokio::task::spawn_blocking(move || -> Result<(u32, u32, Vec<(u32, u32, i64)>)> {
-            let (w, h) = db::get_canvas_dimensions(&db)?;
-            let history = db::get_resize_history(&db)?;
-            Ok((w, h, history))
-        })
-        .await??
-    };
+    let (canvas_width, canvas_height) = store.canvas_dims().await.map_err(Transient)?;
+    let resize_history = store.resize_history().await.map_err(Transient)?;
 
-    // Validate pixel
+    // Validate pixel against the active palette and canvas bounds. A failure here
+    // may be a genuine bad placement or a race against a resize we haven't applied
+    // yet, so it is transient and retried up to the cap.
+    let palette_len = config.canvas.palette().map_err(Permanent)?.len();
     pixel
-        .validate(canvas_width, canvas_height, &resize_history, timestamp)
-        .map_err(|e| anyhow!("Pixel validation failed: {e}"))?;
+        .validate(palette_len, canvas_width, canvas_height, &resize_history, timestamp)
+        .map_err(|e| Transient(anyhow!("Pixel validation failed: {e}")))?;
 
-    // Check credits
+    // Spend one credit and insert the pixel atomically: if the insert fails
+    // transiently and this event is retried, a re-run must not re-spend for a
+    // pixel that was never placed, so the two happen in one transaction.
     let regen_us = config.canvas.credit_regen_seconds as i64 * 1_000_000;
-    let recent_count = {
-        let db = db.clone();
-        let upk = user_pk.to_string();
-        tokio::task::spawn_blocking(move || {
-            db::count_recent_placements(&db, &upk, timestamp, regen_us)
-        })
-        .await??
+    let max_tokens = config.canvas.max_credits as f64;
+    let outcome = {
+        let _db_timer = metrics.db_query_seconds.start_timer();
+        store
+            .spend_credit_and_insert_pixel(
+                pixel_id,
+                user_pk,
+                pixel.x,
+                pixel.y,
+                pixel.color,
+                timestamp,
+                regen_us,
+                max_tokens,
+            )
+            .await
+            .map_err(Transient)?
     };
 
-    if recent_count >= config.canvas.max_credits {
-        return Err(anyhow!(
-            "User {} has no credits (used {}/{})",
-            user_pk,
-            recent_count,
-            config.canvas.max_credits
-        ));
-    }
-
-    // Insert pixel
-    let (was_new, was_overwritten) = {
-        let db = db.clone();
-        let id = pixel_id.to_string();
-        let upk = user_pk.to_string();
-        let px = pixel.clone();
-        tokio::task::spawn_blocking(move || {
-            db::insert_pixel(&db, &id, &upk, px.x, px.y, px.color, timestamp)
-        })
-        .await??
+    let (was_new, was_overwritten, seq) = match outcome {
+        PlacementOutcome::Inserted { was_new, was_overwritten, seq } => {
+            (was_new, was_overwritten, seq)
+        }
+        PlacementOutcome::InsufficientCredits { next_token_us } => {
+            return Err(Permanent(anyhow!(
+                "User {} has no credits (next token in {}s)",
+                user_pk,
+                next_token_us / 1_000_000
+            )));
+        }
     };
 
     info!(
-        "Pixel placed at ({}, {}) color={} by {} (new={}, overwritten={})",
-        pixel.x, pixel.y, pixel.color, user_pk, was_new, was_overwritten
+        "Pixel placed at ({}, {}) color={} by {} (new={}, overwritten={}, seq={})",
+        pixel.x, pixel.y, pixel.color, user_pk, was_new, was_overwritten, seq
     );
 
-    // Broadcast SSE event
-    let _ = sse_tx.send(SseEvent::Pixel(PixelState {
-        x: pixel.x,
-        y: pixel.y,
-        color: pixel.color,
-        user_pk: user_pk.to_string(),
-        placed_at: timestamp,
-    }));
+    // Broadcast SSE event with its changefeed sequence so lagged subscribers can
+    // re-sync from the db instead of silently missing it.
+    metrics.pixels_placed.inc();
+    events.publish(SseEvent::Pixel {
+        pixel: PixelState {
+            x: pixel.x,
+            y: pixel.y,
+            color: pixel.color,
+            user_pk: user_pk.to_string(),
+            placed_at: timestamp,
+        },
+        seq,
+    });
+
+    Ok(())
+}
+
+/// Route a failed ingestion to the retry queue or the dead-letter table.
+/// `attempt` is how many times this pixel has already been tried (0 for the
+/// first live attempt). Permanent failures are dead-lettered immediately;
+/// transient ones are re-enqueued with exponential backoff until the cap.
+async fn handle_failure(
+    store: &dyn CanvasStore,
+    config: &Config,
+    user_pk: &str,
+    pixel_id: &str,
+    uri: &str,
+    attempt: u32,
+    err: ProcessError,
+) -> Result<()> {
+    let now = pixel::timestamp_micros();
+    let reason = err.reason();
+
+    match err {
+        ProcessError::Permanent(e) => {
+            warn!("Dead-lettering pixel {pixel_id} from {user_pk}: {e}");
+            store.dead_letter(pixel_id, user_pk, uri, &reason, now).await?;
+        }
+        ProcessError::Transient(e) => {
+            let next_attempt = attempt + 1;
+            if next_attempt >= config.watcher.retry_max_attempts {
+                warn!("Pixel {pixel_id} from {user_pk} exhausted retries: {e}");
+                store.dead_letter(pixel_id, user_pk, uri, &reason, now).await?;
+            } else {
+                // Exponential backoff on the number of attempts already made.
+                let backoff_us =
+                    config.watcher.retry_base_seconds as i64 * 1_000_000 * (1i64 << attempt);
+                let next_retry_at = now + backoff_us;
+                warn!(
+                    "Retrying pixel {pixel_id} from {user_pk} (attempt {next_attempt}) in {}s: {e}",
+                    backoff_us / 1_000_000
+                );
+                store
+                    .enqueue_retry(pixel_id, user_pk, uri, next_attempt, next_retry_at)
+                    .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Drain retry-queue entries that are due, re-running `process_pixel_event` for
+/// each and either clearing it on success or rescheduling/dead-lettering it.
+async fn drain_retries(
+    store: &dyn CanvasStore,
+    pubky: &Pubky,
+    config: &Config,
+    events: &dyn EventBus,
+    metrics: &Arc<Metrics>,
+) -> Result<()> {
+    let now = pixel::timestamp_micros();
+    let due = store.due_retries(now).await?;
+
+    for entry in due {
+        match process_pixel_event(
+            store,
+            pubky,
+            config,
+            events,
+            metrics,
+            &entry.user_pk,
+            &entry.pixel_id,
+            &entry.uri,
+        )
+        .await
+        {
+            Ok(()) => {
+                store.remove_retry(&entry.pixel_id).await?;
+                debug!("Retried pixel {} successfully", entry.pixel_id);
+            }
+            Err(err) => {
+                handle_failure(
+                    store,
+                    config,
+                    &entry.user_pk,
+                    &entry.pixel_id,
+                    &entry.uri,
+                    entry.attempt_count,
+                    err,
+                )
+                .await?;
+            }
+        }
+    }
 
     Ok(())
 }
 
 async fn check_resize(
-    db: &Db,
-    _config: &Config,
-    sse_tx: &broadcast::Sender<SseEvent>,
+    store: &Arc<dyn CanvasStore>,
+    config: &Config,
+    events: &dyn EventBus,
 ) -> Result<()> {
-    let (canvas_width, canvas_height, filled, overwritten) = {
-        let db = db.clone();
-        tokio::task::spawn_blocking(move || -> Result<(u32, u32, u32, u32)> {
-            let (w, h) = db::get_canvas_dimensions(&db)?;
-            let (filled, overwritten) = db::get_fill_stats(&db)?;
-            Ok((w, h, filled, overwritten))
-        })
-        .await??
-    };
+    let (canvas_width, canvas_height) = store.canvas_dims().await?;
+    let (filled, overwritten) = store.fill_stats().await?;
 
     let total_pixels = canvas_width * canvas_height;
     let half_pixels = total_pixels / 2;
@@ -357,16 +565,151 @@ async fn check_resize(
             canvas_width, canvas_height, new_width, new_height, filled, overwritten, half_pixels
         );
 
-        let db = db.clone();
-        tokio::task::spawn_blocking(move || db::resize_canvas(&db, new_width, new_height, now)).await??;
+        let seq = store
+            .resize_canvas(canvas_width, canvas_height, new_width, new_height, now)
+            .await?;
 
-        let _ = sse_tx.send(SseEvent::Resize {
+        events.publish(SseEvent::Resize {
             old_width: canvas_width,
             old_height: canvas_height,
             new_width,
             new_height,
+            seq,
         });
+
+        // Capture a frame at each resize so the timelapse has a snapshot of every
+        // board generation, independent of the periodic interval.
+        if let Some(frames) = &config.frames {
+            let palette = config.canvas.palette()?;
+            match crate::frames::capture(frames, &palette, store).await {
+                Ok(path) => info!("Wrote resize frame {}", path.display()),
+                Err(e) => warn!("Failed to write resize frame: {e}"),
+            }
+        }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed `chunks` through the parser exactly as `poll_homeserver` does —
+    /// `push_bytes` per chunk, then a final `finish` — and collect every event.
+    fn drive(chunks: &[&[u8]]) -> Vec<SseEventParsed> {
+        let mut parser = SseStreamParser::default();
+        let mut events = Vec::new();
+        for chunk in chunks {
+            events.extend(parser.push_bytes(chunk));
+        }
+        if let Some(event) = parser.finish() {
+            events.push(event);
+        }
+        events
+    }
+
+    #[test]
+    fn reassembles_event_split_across_chunks() {
+        // The newline after `event: PUT` lands in the second chunk, so the first
+        // read ends mid-line; the parser must hold it back rather than emit.
+        let events = drive(&[
+            b"event: PU",
+            b"T\ndata: pubky://alice/pub/pubky-canva/pixels/p1\ndata: cursor: 10\n\n",
+        ]);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, "PUT");
+        assert_eq!(events[0].uri, "pubky://alice/pub/pubky-canva/pixels/p1");
+        assert_eq!(events[0].cursor, "10");
+    }
+
+    #[test]
+    fn reassembles_data_line_split_across_chunks() {
+        // Split in the middle of the `data:` URI line.
+        let events = drive(&[
+            b"event: PUT\ndata: pubky://alice/pub/pubky-ca",
+            b"nva/pixels/p1\ndata: cursor: 7\n\n",
+        ]);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].uri, "pubky://alice/pub/pubky-canva/pixels/p1");
+        assert_eq!(events[0].cursor, "7");
+    }
+
+    #[test]
+    fn rejoins_utf8_sequence_split_across_chunks() {
+        // "café" — the two bytes of 'é' (0xC3 0xA9) straddle the chunk boundary.
+        // Because whole lines are only decoded once newline-terminated, the split
+        // character is rejoined instead of being lossily replaced.
+        let line = "data: pubky://café/pub/pubky-canva/pixels/p1\n";
+        let bytes = format!("event: PUT\n{line}data: cursor: 3\n\n").into_bytes();
+        let split = bytes.iter().position(|&b| b == 0xC3).unwrap() + 1;
+        let events = drive(&[&bytes[..split], &bytes[split..]]);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].uri, "pubky://café/pub/pubky-canva/pixels/p1");
+    }
+
+    /// Canned events-stream body a homeserver might emit, handed out in
+    /// deliberately awkward fragments: mid-event, mid-`data:` line, and
+    /// mid-UTF8-sequence. Mirrors the `bytes_stream()` the watcher consumes.
+    struct MockHomeserver {
+        chunks: Vec<Vec<u8>>,
+    }
+
+    impl MockHomeserver {
+        /// Two PUT events whose cursors advance 10 → 11, fragmented at every
+        /// boundary the parser has to survive.
+        fn two_pixels() -> Self {
+            let body = concat!(
+                "event: PUT\n",
+                "data: pubky://café/pub/pubky-canva/pixels/p1\n",
+                "data: cursor: 10\n",
+                "data: content_hash: abc\n",
+                "\n",
+                "event: PUT\n",
+                "data: pubky://alice/pub/pubky-canva/pixels/p2\n",
+                "data: cursor: 11\n",
+                "\n",
+            )
+            .as_bytes()
+            .to_vec();
+
+            // Hand the body out one byte at a time so cuts land inside every
+            // line and event block, and between the two bytes of 'é'.
+            let chunks = body.chunks(1).map(<[u8]>::to_vec).collect();
+            MockHomeserver { chunks }
+        }
+
+        fn chunks(&self) -> Vec<&[u8]> {
+            self.chunks.iter().map(Vec::as_slice).collect()
+        }
+    }
+
+    #[test]
+    fn mock_homeserver_yields_exact_events_and_advances_cursor() {
+        let hs = MockHomeserver::two_pixels();
+        let events = drive(&hs.chunks());
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].uri, "pubky://café/pub/pubky-canva/pixels/p1");
+        assert_eq!(events[0].cursor, "10");
+        assert_eq!(events[1].uri, "pubky://alice/pub/pubky-canva/pixels/p2");
+        assert_eq!(events[1].cursor, "11");
+
+        // The last cursor the watcher would persist is the newest one.
+        let latest = events.last().map(|e| e.cursor.clone()).unwrap();
+        assert_eq!(latest, "11");
+
+        // content_hash lines are metadata, never a URI.
+        assert!(events.iter().all(|e| !e.uri.contains("content_hash")));
+    }
+
+    #[test]
+    fn parse_pixel_uri_extracts_user_and_id() {
+        let (user, id) =
+            parse_pixel_uri("pubky://alice/pub/pubky-canva/pixels/p1").unwrap();
+        assert_eq!(user, "alice");
+        assert_eq!(id, "p1");
+        assert!(parse_pixel_uri("pubky://alice/pub/pubky-canva/pixels/").is_none());
+        assert!(parse_pixel_uri("https://example.com/foo").is_none());
+    }
+}